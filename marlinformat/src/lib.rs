@@ -5,6 +5,8 @@ use cozy_chess::{BitBoard, Board, BoardBuilder, Color, Piece, Rank, Square};
 
 const UNMOVED_ROOK: u8 = Piece::NUM as u8;
 
+// `eval` and `wdl` are white relative by convention. Data written side to move relative must be
+// read with a matching score perspective.
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[repr(C)]
 pub struct PackedBoard {
@@ -140,12 +142,12 @@ mod util {
 
     impl U4Array32 {
         pub fn get(&self, i: usize) -> u8 {
-            (self.0[i / 2] >> (i % 2) * 4) & 0xF
+            (self.0[i / 2] >> ((i % 2) * 4)) & 0xF
         }
 
         pub fn set(&mut self, i: usize, v: u8) {
             debug_assert!(v < 0x10);
-            self.0[i / 2] |= v << (i % 2) * 4;
+            self.0[i / 2] |= v << ((i % 2) * 4);
         }
     }
 }
@@ -0,0 +1,235 @@
+#ifndef PARSE_H
+#define PARSE_H
+
+/* Generated by cbindgen from the parse crate. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CustomMaterial_MAX_BUCKETS 64
+
+#define FilterRule_NUM 4
+
+typedef enum BatchLayout {
+  BatchLayout_Sparse,
+  BatchLayout_Cuda,
+  BatchLayout_Csr,
+} BatchLayout;
+
+typedef enum FilterRule {
+  FilterRule_InCheck,
+  FilterRule_Eval,
+  FilterRule_WdlDisagreement,
+  FilterRule_Capture,
+} FilterRule;
+
+typedef enum LambdaSchedule {
+  LambdaSchedule_Constant,
+  LambdaSchedule_Phase,
+  LambdaSchedule_Eval,
+} LambdaSchedule;
+
+typedef enum LoaderStage {
+  LoaderStage_Io,
+  LoaderStage_Unpack,
+  LoaderStage_Featurize,
+  LoaderStage_Busy,
+  LoaderStage_Parse,
+} LoaderStage;
+
+typedef enum ScorePerspective {
+  ScorePerspective_White,
+  ScorePerspective_SideToMove,
+} ScorePerspective;
+
+typedef struct Batch Batch;
+
+typedef struct BatchReader BatchReader;
+
+typedef struct CustomMaterial CustomMaterial;
+
+typedef struct KingBucketTable KingBucketTable;
+
+typedef struct LoaderConfig LoaderConfig;
+
+typedef uint32_t BucketingSchemeType;
+
+typedef uint32_t InputFeatureSetType;
+
+uint32_t batch_get_capacity(struct Batch *batch);
+
+uint32_t batch_get_len(struct Batch *batch);
+
+const int64_t *batch_get_stm_feature_buffer_ptr(struct Batch *batch);
+
+const int64_t *batch_get_nstm_feature_buffer_ptr(struct Batch *batch);
+
+const float *batch_get_values_ptr(struct Batch *batch);
+
+const int64_t *batch_get_offsets_ptr(struct Batch *batch);
+
+uint32_t batch_get_total_features(struct Batch *batch);
+
+uint32_t batch_get_indices_per_feature(struct Batch *batch);
+
+const float *batch_get_cp_ptr(struct Batch *batch);
+
+const float *batch_get_wdl_ptr(struct Batch *batch);
+
+const float *batch_get_target_ptr(struct Batch *batch);
+
+const float *batch_get_weight_ptr(struct Batch *batch);
+
+const int32_t *batch_get_bucket_ptr(struct Batch *batch);
+
+uint32_t batch_get_bucket_outputs(struct Batch *batch);
+
+const bool *batch_get_stm_mirrored_ptr(struct Batch *batch);
+
+const bool *batch_get_nstm_mirrored_ptr(struct Batch *batch);
+
+void loader_config_set_mmap(struct LoaderConfig *config, bool value);
+
+void loader_config_set_shuffle_chunks(struct LoaderConfig *config, bool value);
+
+void loader_config_set_seed(struct LoaderConfig *config, uint64_t value);
+
+void loader_config_set_epoch(struct LoaderConfig *config, uint64_t value);
+
+void loader_config_set_batches_per_chunk(struct LoaderConfig *config, uint32_t value);
+
+void loader_config_set_prefetch_depth(struct LoaderConfig *config, uint32_t value);
+
+void loader_config_set_threads(struct LoaderConfig *config, uint32_t value);
+
+void loader_config_set_collect_timings(struct LoaderConfig *config, bool value);
+
+bool loader_config_set_score_perspective(struct LoaderConfig *config, uint32_t value);
+
+void loader_config_set_syzygy_max_pieces(struct LoaderConfig *config, uint32_t value);
+
+void loader_config_set_syzygy_rescore_cp(struct LoaderConfig *config, bool value);
+
+void loader_config_set_syzygy_win_cp(struct LoaderConfig *config, float value);
+
+void loader_config_set_augment_mirror_probability(struct LoaderConfig *config, double value);
+
+void loader_config_set_augment_color_flip_probability(struct LoaderConfig *config, double value);
+
+void loader_config_set_augment_seed(struct LoaderConfig *config, uint64_t value);
+
+void loader_config_set_keep_probability(struct LoaderConfig *config, double value);
+
+void loader_config_set_min_fullmove(struct LoaderConfig *config, uint16_t value);
+
+void loader_config_set_filter_in_check(struct LoaderConfig *config, bool value);
+
+void loader_config_set_filter_max_eval(struct LoaderConfig *config, float value);
+
+void loader_config_set_filter_wdl_margin(struct LoaderConfig *config, float value);
+
+void loader_config_set_filter_captures(struct LoaderConfig *config, bool value);
+
+void loader_config_set_weight_early_fullmove(struct LoaderConfig *config, uint16_t value);
+
+void loader_config_set_weight_early(struct LoaderConfig *config, float value);
+
+void loader_config_set_weight_disagreement_margin(struct LoaderConfig *config, float value);
+
+void loader_config_set_weight_disagreement(struct LoaderConfig *config, float value);
+
+void loader_config_set_weight_balance_buckets(struct LoaderConfig *config, bool value);
+
+void loader_config_set_target_scale(struct LoaderConfig *config, float value);
+
+void loader_config_set_target_wdl_lambda(struct LoaderConfig *config, float value);
+
+void loader_config_set_target_wdl_lambda_end(struct LoaderConfig *config, float value);
+
+bool loader_config_set_target_lambda_schedule(struct LoaderConfig *config, uint32_t value);
+
+void loader_config_set_target_eval_limit(struct LoaderConfig *config, float value);
+
+bool loader_config_set_syzygy_path(struct LoaderConfig *config, const char *path);
+
+struct LoaderConfig *loader_config_new(void);
+
+void loader_config_drop(struct LoaderConfig *config);
+
+void loader_config_set_custom_material(struct LoaderConfig *config,
+                                       const struct CustomMaterial *custom_material);
+
+bool loader_config_add_bucketing_scheme(struct LoaderConfig *config,
+                                        BucketingSchemeType bucketing_scheme);
+
+void loader_config_add_custom_material(struct LoaderConfig *config,
+                                       const struct CustomMaterial *custom_material);
+
+struct CustomMaterial *custom_material_new(const uint32_t *piece_values,
+                                           const uint32_t *thresholds,
+                                           uint32_t threshold_count);
+
+void custom_material_drop(struct CustomMaterial *custom_material);
+
+uint32_t custom_material_get_bucket_count(const struct CustomMaterial *custom_material);
+
+void loader_config_set_king_buckets(struct LoaderConfig *config,
+                                    const struct KingBucketTable *king_buckets);
+
+struct KingBucketTable *king_bucket_table_new(const uint32_t *buckets);
+
+void king_bucket_table_drop(struct KingBucketTable *king_buckets);
+
+uint32_t king_bucket_table_get_bucket_count(const struct KingBucketTable *king_buckets);
+
+struct BatchReader *batch_reader_new(const char *path,
+                                     uint32_t batch_size,
+                                     InputFeatureSetType feature_set,
+                                     uint32_t layout,
+                                     BucketingSchemeType bucketing_scheme,
+                                     const struct LoaderConfig *config);
+
+uint64_t batch_reader_dataset_size(struct BatchReader *reader);
+
+uint64_t batch_reader_rescored_positions(struct BatchReader *reader);
+
+uint64_t batch_reader_filtered_positions(struct BatchReader *reader, uint32_t rule);
+
+uint64_t batch_reader_stage_nanos(struct BatchReader *reader, uint32_t stage);
+
+uint32_t batch_reader_threads(struct BatchReader *reader);
+
+void batch_reader_drop(struct BatchReader *reader);
+
+uint32_t parse_abi_version(void);
+
+uint32_t input_feature_set_count(void);
+
+int32_t input_feature_set_from_name(const char *name);
+
+uint32_t input_feature_set_get_max_features(InputFeatureSetType feature_set);
+
+uint32_t input_feature_set_get_num_inputs(InputFeatureSetType feature_set);
+
+uint32_t input_feature_set_get_king_buckets(InputFeatureSetType feature_set);
+
+const char *input_feature_set_get_name(InputFeatureSetType feature_set);
+
+const char *input_feature_set_get_description(InputFeatureSetType feature_set);
+
+uint32_t batch_layout_get_indices_per_feature(uint32_t layout);
+
+uint32_t bucketing_scheme_count(void);
+
+int32_t bucketing_scheme_from_name(const char *name);
+
+const char *bucketing_scheme_get_name(BucketingSchemeType bucketing_scheme);
+
+uint32_t bucketing_scheme_get_bucket_count(BucketingSchemeType bucketing_scheme);
+
+struct Batch *read_batch(struct BatchReader *reader);
+
+#endif /* PARSE_H */
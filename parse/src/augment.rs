@@ -0,0 +1,91 @@
+use cozy_chess::{Board, BoardBuilder, Color, Rank, Square};
+use rand::Rng;
+
+pub struct Augmentation {
+    pub mirror_probability: f64,
+    pub color_flip_probability: f64,
+    pub seed: u64,
+}
+
+impl Augmentation {
+    // Color flipping keeps side to move relative scores unchanged, so only the board is replaced
+    pub fn apply(&self, board: &mut Board, rng: &mut impl Rng) {
+        if self.mirror_probability > 0.0
+            && rng.gen_bool(self.mirror_probability)
+            && no_castle_rights(board)
+        {
+            if let Some(mirrored) = transform(board, Square::flip_file, false) {
+                *board = mirrored;
+            }
+        }
+        if self.color_flip_probability > 0.0 && rng.gen_bool(self.color_flip_probability) {
+            if let Some(flipped) = transform(board, Square::flip_rank, true) {
+                *board = flipped;
+            }
+        }
+    }
+}
+
+// Mirroring across the d/e file would move castling rooks and kings to the wrong files
+fn no_castle_rights(board: &Board) -> bool {
+    Color::ALL.iter().all(|&color| {
+        let rights = board.castle_rights(color);
+        rights.short.is_none() && rights.long.is_none()
+    })
+}
+
+fn transform(board: &Board, map: fn(Square) -> Square, flip_colors: bool) -> Option<Board> {
+    let flip = |color: Color| match flip_colors {
+        true => !color,
+        false => color,
+    };
+
+    let mut builder = BoardBuilder::empty();
+    for square in board.occupied() {
+        let piece = board.piece_on(square)?;
+        let color = board.color_on(square)?;
+        builder.board[map(square) as usize] = Some((piece, flip(color)));
+    }
+    for &color in &Color::ALL {
+        let rights = board.castle_rights(color);
+        let flipped_rights = builder.castle_rights_mut(flip(color));
+        flipped_rights.short = rights.short;
+        flipped_rights.long = rights.long;
+    }
+    let stm = board.side_to_move();
+    builder.en_passant = board
+        .en_passant()
+        .map(|file| map(Square::new(file, Rank::Sixth.relative_to(stm))));
+    builder.side_to_move = flip(stm);
+    builder.halfmove_clock = board.halfmove_clock();
+    builder.fullmove_number = board.fullmove_number();
+
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms() {
+        let fen = "4k3/8/8/3pP3/8/8/1N6/4K2R w K d6 0 2";
+        let board: Board = fen.parse().unwrap();
+        let flipped = transform(&board, Square::flip_rank, true).unwrap();
+        assert_eq!(
+            format!("{}", flipped),
+            "4k2r/1n6/8/8/3Pp3/8/8/4K3 b k d3 0 2"
+        );
+        let flipped_back = transform(&flipped, Square::flip_rank, true).unwrap();
+        assert_eq!(format!("{}", flipped_back), fen);
+        assert!(!no_castle_rights(&board));
+
+        let board: Board = "4k3/8/8/3pP3/8/8/1N6/4K2R w - d6 0 2".parse().unwrap();
+        assert!(no_castle_rights(&board));
+        let mirrored = transform(&board, Square::flip_file, false).unwrap();
+        assert_eq!(
+            format!("{}", mirrored),
+            "3k4/8/8/3Pp3/8/8/6N1/R2K4 w - e6 0 2"
+        );
+    }
+}
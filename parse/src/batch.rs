@@ -1,51 +1,145 @@
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BatchLayout {
+    // `[entry, feature]` pairs
+    Sparse,
+    // `max_features` indices per entry, padded with -1
+    Cuda,
+    // Features of all entries back to back, delimited by `offsets`
+    Csr,
+}
+
+impl BatchLayout {
+    pub const ALL: [BatchLayout; 3] = [BatchLayout::Sparse, BatchLayout::Cuda, BatchLayout::Csr];
+
+    pub fn indices_per_feature(self) -> usize {
+        match self {
+            BatchLayout::Sparse => 2,
+            BatchLayout::Cuda | BatchLayout::Csr => 1,
+        }
+    }
+}
+
 pub struct Batch {
     // The maximum number of entries
     capacity: usize,
 
     max_features: usize,
+    layout: BatchLayout,
 
     stm_feature_buffer: Box<[i64]>,
     nstm_feature_buffer: Box<[i64]>,
     values: Box<[f32]>,
+    // The index of the first feature of each entry
+    offsets: Box<[i64]>,
     total_features: usize,
     indices_per_feature: usize,
 
     cp: Box<[f32]>,
     wdl: Box<[f32]>,
+    weights: Box<[f32]>,
+    targets: Box<[f32]>,
+    // `bucket_outputs` buckets per entry
     buckets: Box<[i32]>,
+    bucket_outputs: usize,
+    stm_mirrored: Box<[bool]>,
+    nstm_mirrored: Box<[bool]>,
 
     // The number of entries actually written
     entries: usize,
 }
 
 impl Batch {
-    pub fn new(capacity: usize, max_features: usize, indices_per_feature: usize) -> Self {
+    pub fn new(
+        capacity: usize,
+        max_features: usize,
+        layout: BatchLayout,
+        bucket_outputs: usize,
+    ) -> Self {
+        let indices_per_feature = layout.indices_per_feature();
         Self {
             capacity,
             max_features,
+            layout,
             stm_feature_buffer: vec![0; capacity * max_features * indices_per_feature]
                 .into_boxed_slice(),
             nstm_feature_buffer: vec![0; capacity * max_features * indices_per_feature]
                 .into_boxed_slice(),
+            offsets: vec![0; capacity].into_boxed_slice(),
             total_features: 0,
             indices_per_feature,
             values: vec![1.0; capacity * max_features].into_boxed_slice(),
             cp: vec![0_f32; capacity].into_boxed_slice(),
             wdl: vec![0_f32; capacity].into_boxed_slice(),
-            buckets: vec![0; capacity].into_boxed_slice(),
+            weights: vec![1_f32; capacity].into_boxed_slice(),
+            targets: vec![0_f32; capacity].into_boxed_slice(),
+            buckets: vec![0; capacity * bucket_outputs].into_boxed_slice(),
+            bucket_outputs,
+            stm_mirrored: vec![false; capacity].into_boxed_slice(),
+            nstm_mirrored: vec![false; capacity].into_boxed_slice(),
             entries: 0,
         }
     }
 
-    pub fn make_entry(&mut self, cp: f32, wdl: f32, bucket: i32) -> EntryFeatureWriter {
+    // Bytes allocated per entry by `new`
+    pub fn entry_bytes(max_features: usize, layout: BatchLayout, bucket_outputs: usize) -> usize {
+        let indices = 2 * max_features * layout.indices_per_feature();
+        indices * std::mem::size_of::<i64>()
+            + max_features * std::mem::size_of::<f32>()
+            + std::mem::size_of::<i64>()
+            + 4 * std::mem::size_of::<f32>()
+            + bucket_outputs * std::mem::size_of::<i32>()
+            + 2 * std::mem::size_of::<bool>()
+    }
+
+    pub fn make_entry(
+        &mut self,
+        cp: f32,
+        wdl: f32,
+        target: f32,
+        weight: f32,
+        buckets: impl Iterator<Item = i32>,
+    ) -> EntryFeatureWriter<'_> {
         let index_in_batch = self.entries;
         self.entries += 1;
         self.cp[index_in_batch] = cp;
         self.wdl[index_in_batch] = wdl;
-        self.buckets[index_in_batch] = bucket;
+        self.targets[index_in_batch] = target;
+        self.weights[index_in_batch] = weight;
+        self.offsets[index_in_batch] = self.total_features as i64;
+        let bucket_row = index_in_batch * self.bucket_outputs;
+        for (slot, bucket) in self.buckets[bucket_row..bucket_row + self.bucket_outputs]
+            .iter_mut()
+            .zip(buckets)
+        {
+            *slot = bucket;
+        }
+        self.stm_mirrored[index_in_batch] = false;
+        self.nstm_mirrored[index_in_batch] = false;
         EntryFeatureWriter {
             batch: self,
             index_in_batch,
+            count: 0,
+            factorizer: None,
+        }
+    }
+
+    // Scales weights so every primary bucket present in the batch carries the same total weight
+    pub fn balance_bucket_weights(&mut self) {
+        let bucket_outputs = self.bucket_outputs;
+        let primary_bucket = |buckets: &[i32], i: usize| buckets[i * bucket_outputs] as usize;
+        let mut counts = vec![];
+        for i in 0..self.entries {
+            let bucket = primary_bucket(&self.buckets, i);
+            if bucket >= counts.len() {
+                counts.resize(bucket + 1, 0);
+            }
+            counts[bucket] += 1;
+        }
+        let present = counts.iter().filter(|&&count| count > 0).count();
+        for i in 0..self.entries {
+            let count = counts[primary_bucket(&self.buckets, i)];
+            self.weights[i] *= self.entries as f32 / (present * count) as f32;
         }
     }
 
@@ -74,6 +168,10 @@ impl Batch {
         &self.values[0]
     }
 
+    pub fn offsets_ptr(&self) -> *const i64 {
+        self.offsets.as_ptr()
+    }
+
     pub fn total_features(&self) -> usize {
         self.total_features
     }
@@ -90,58 +188,76 @@ impl Batch {
         &self.wdl[0]
     }
 
-    pub fn bucket_ptr(&self) -> *const i32 {
-        self.buckets.as_ptr()
+    pub fn target_ptr(&self) -> *const f32 {
+        self.targets.as_ptr()
     }
-}
 
-pub struct SparseBatchWriter<'b> {
-    entry_feature_writer: EntryFeatureWriter<'b>,
-}
+    pub fn weight_ptr(&self) -> *const f32 {
+        self.weights.as_ptr()
+    }
 
-impl SparseBatchWriter<'_> {
-    pub fn add_feature(&mut self, stm_feature: i64, nstm_feature: i64) {
-        self.entry_feature_writer
-            .add_feature_sparse(stm_feature, nstm_feature);
+    pub fn bucket_ptr(&self) -> *const i32 {
+        self.buckets.as_ptr()
     }
-}
 
-pub struct CudaBatchWriter<'b> {
-    entry_feature_writer: EntryFeatureWriter<'b>,
-    count: usize,
-}
+    pub fn bucket_outputs(&self) -> usize {
+        self.bucket_outputs
+    }
 
-impl CudaBatchWriter<'_> {
-    pub fn add_feature(&mut self, stm_feature: i64, nstm_feature: i64) {
-        self.entry_feature_writer
-            .add_feature_cuda(stm_feature, nstm_feature);
-        self.count += 1;
+    pub fn stm_mirrored_ptr(&self) -> *const bool {
+        self.stm_mirrored.as_ptr()
     }
-}
 
-impl<'b> Drop for CudaBatchWriter<'b> {
-    fn drop(&mut self) {
-        self.entry_feature_writer.complete_cuda(self.count);
+    pub fn nstm_mirrored_ptr(&self) -> *const bool {
+        self.nstm_mirrored.as_ptr()
     }
 }
 
 pub struct EntryFeatureWriter<'b> {
     batch: &'b mut Batch,
     index_in_batch: usize,
+    // The number of features written for this entry
+    count: usize,
+    // Offset and size of the virtual feature index space
+    factorizer: Option<(i64, i64)>,
 }
 
-impl<'b> EntryFeatureWriter<'b> {
-    pub fn sparse(self) -> SparseBatchWriter<'b> {
-        SparseBatchWriter {
-            entry_feature_writer: self,
+impl EntryFeatureWriter<'_> {
+    pub fn add_feature(&mut self, stm_feature: i64, nstm_feature: i64) {
+        self.add_feature_with_layout(stm_feature, nstm_feature);
+        if let Some((stm_feature, nstm_feature)) = self.virtual_feature(stm_feature, nstm_feature) {
+            self.add_feature_with_layout(stm_feature, nstm_feature);
         }
     }
 
-    pub fn cuda(self) -> CudaBatchWriter<'b> {
-        CudaBatchWriter {
-            entry_feature_writer: self,
-            count: 0,
+    // Makes every feature added also add the virtual feature `offset + feature % inputs`
+    pub fn factorized(mut self, offset: usize, inputs: usize) -> Self {
+        self.factorizer = Some((offset as i64, inputs as i64));
+        self
+    }
+
+    // Records whether the features of each perspective were mirrored across the d/e file
+    pub fn set_mirrored(&mut self, stm: bool, nstm: bool) {
+        self.batch.stm_mirrored[self.index_in_batch] = stm;
+        self.batch.nstm_mirrored[self.index_in_batch] = nstm;
+    }
+
+    fn virtual_feature(&self, stm_feature: i64, nstm_feature: i64) -> Option<(i64, i64)> {
+        let (offset, inputs) = self.factorizer?;
+        Some((
+            offset + stm_feature % inputs,
+            offset + nstm_feature % inputs,
+        ))
+    }
+
+    fn add_feature_with_layout(&mut self, stm_feature: i64, nstm_feature: i64) {
+        match self.batch.layout {
+            BatchLayout::Sparse => self.add_feature_sparse(stm_feature, nstm_feature),
+            BatchLayout::Cuda | BatchLayout::Csr => {
+                self.add_feature_dense(stm_feature, nstm_feature)
+            }
         }
+        self.count += 1;
     }
 
     fn add_feature_sparse(&mut self, stm_feature: i64, nstm_feature: i64) {
@@ -153,14 +269,14 @@ impl<'b> EntryFeatureWriter<'b> {
         self.batch.total_features += 1;
     }
 
-    fn add_feature_cuda(&mut self, stm_feature: i64, nstm_feature: i64) {
+    fn add_feature_dense(&mut self, stm_feature: i64, nstm_feature: i64) {
         self.batch.stm_feature_buffer[self.batch.total_features] = stm_feature;
         self.batch.nstm_feature_buffer[self.batch.total_features] = nstm_feature;
         self.batch.total_features += 1;
     }
 
-    fn complete_cuda(&mut self, count: usize) {
-        let left_to_fill = self.batch.max_features - count;
+    fn complete_cuda(&mut self) {
+        let left_to_fill = self.batch.max_features - self.count;
         for _ in 0..left_to_fill {
             self.batch.stm_feature_buffer[self.batch.total_features] = -1;
             self.batch.nstm_feature_buffer[self.batch.total_features] = -1;
@@ -168,3 +284,11 @@ impl<'b> EntryFeatureWriter<'b> {
         }
     }
 }
+
+impl Drop for EntryFeatureWriter<'_> {
+    fn drop(&mut self) {
+        if self.batch.layout == BatchLayout::Cuda {
+            self.complete_cuda();
+        }
+    }
+}
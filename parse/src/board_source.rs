@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::Read;
+
+use bytemuck::Zeroable;
+use marlinformat::PackedBoard;
+use memmap2::Mmap;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+const BOARD_SIZE: usize = std::mem::size_of::<PackedBoard>();
+
+pub enum BoardSource {
+    Buffered {
+        file: File,
+        buffer: Vec<PackedBoard>,
+    },
+    Mapped {
+        map: Mmap,
+        chunk_size: usize,
+        chunks: Vec<usize>,
+        next: usize,
+    },
+}
+
+impl BoardSource {
+    pub fn buffered(file: File, chunk_size: usize) -> Self {
+        BoardSource::Buffered {
+            file,
+            buffer: vec![PackedBoard::zeroed(); chunk_size],
+        }
+    }
+
+    pub fn mapped(
+        file: &File,
+        chunk_size: usize,
+        shuffle: Option<StdRng>,
+    ) -> std::io::Result<Self> {
+        let map = unsafe { Mmap::map(file)? };
+        let boards = map.len() / BOARD_SIZE;
+        let mut chunks: Vec<_> = (0..boards.div_ceil(chunk_size)).collect();
+        if let Some(mut rng) = shuffle {
+            chunks.shuffle(&mut rng);
+        }
+        Ok(BoardSource::Mapped {
+            map,
+            chunk_size,
+            chunks,
+            next: 0,
+        })
+    }
+
+    pub fn next_chunk(&mut self) -> Option<&[PackedBoard]> {
+        match self {
+            BoardSource::Buffered { file, buffer } => {
+                let bytes = bytemuck::cast_slice_mut(buffer);
+                let mut bytes_read = 0;
+                loop {
+                    match file.read(&mut bytes[bytes_read..]) {
+                        Ok(0) => break,
+                        Ok(some) => bytes_read += some,
+                        Err(_) => return None,
+                    }
+                }
+                let elems = bytes_read / BOARD_SIZE;
+                match elems {
+                    0 => None,
+                    _ => Some(&buffer[..elems]),
+                }
+            }
+            BoardSource::Mapped {
+                map,
+                chunk_size,
+                chunks,
+                next,
+            } => {
+                let chunk = *chunks.get(*next)?;
+                *next += 1;
+                let boards = map.len() / BOARD_SIZE;
+                let start = chunk * *chunk_size;
+                let end = (start + *chunk_size).min(boards);
+                Some(bytemuck::cast_slice(
+                    &map[start * BOARD_SIZE..end * BOARD_SIZE],
+                ))
+            }
+        }
+    }
+}
@@ -1,26 +1,34 @@
-use cozy_chess::{Board, Piece};
+use cozy_chess::{Board, Color, File, Piece, Rank};
 
-#[repr(C)]
-pub enum BucketingSchemeType {
-    NoBucketing,
-    ModifiedMaterial,
-    PieceCount,
+use crate::registry::BucketingSchemeType;
+
+pub trait BucketingScheme {
+    const BUCKET_COUNT: usize;
+
+    fn bucket(board: &Board) -> i32;
 }
 
-impl BucketingSchemeType {
-    pub fn bucket_count(self) -> usize {
+// The bucketing applied by a loader, either a registered scheme or one configured at runtime
+#[derive(Clone, Debug)]
+pub enum Bucketing {
+    Scheme(BucketingSchemeType),
+    CustomMaterial(CustomMaterial),
+}
+
+impl Bucketing {
+    pub fn bucket_count(&self) -> usize {
         match self {
-            BucketingSchemeType::NoBucketing => NoBucketing::BUCKET_COUNT,
-            BucketingSchemeType::ModifiedMaterial => ModifiedMaterial::BUCKET_COUNT,
-            BucketingSchemeType::PieceCount => PieceCount::BUCKET_COUNT,
+            Bucketing::Scheme(scheme) => scheme.bucket_count(),
+            Bucketing::CustomMaterial(custom) => custom.bucket_count(),
         }
     }
-}
-
-pub trait BucketingScheme {
-    const BUCKET_COUNT: usize;
 
-    fn bucket(board: &Board) -> i32;
+    pub fn bucket(&self, board: &Board) -> i32 {
+        match self {
+            Bucketing::Scheme(scheme) => scheme.bucket(board),
+            Bucketing::CustomMaterial(custom) => custom.bucket(board),
+        }
+    }
 }
 
 pub struct NoBucketing;
@@ -49,6 +57,51 @@ impl BucketingScheme for ModifiedMaterial {
     }
 }
 
+// ModifiedMaterial with configurable piece values and bucket boundaries. Material reaching a
+// threshold moves the position into the next bucket.
+#[derive(Clone, Debug)]
+pub struct CustomMaterial {
+    // Pawn, knight, bishop, rook, queen
+    piece_values: [u32; 5],
+    thresholds: Box<[u32]>,
+}
+
+impl CustomMaterial {
+    pub const MAX_BUCKETS: usize = 64;
+
+    pub fn new(piece_values: [u32; 5], thresholds: &[u32]) -> Option<Self> {
+        let increasing = thresholds.windows(2).all(|pair| pair[0] < pair[1]);
+        // A threshold of 0 would leave the first bucket empty
+        if !increasing || thresholds.first() == Some(&0) || thresholds.len() >= Self::MAX_BUCKETS {
+            return None;
+        }
+        Some(Self {
+            piece_values,
+            thresholds: thresholds.into(),
+        })
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.thresholds.len() + 1
+    }
+
+    pub fn bucket(&self, board: &Board) -> i32 {
+        let material: u64 = [
+            Piece::Pawn,
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Rook,
+            Piece::Queen,
+        ]
+        .iter()
+        .zip(&self.piece_values)
+        .map(|(&piece, &value)| board.pieces(piece).len() as u64 * value as u64)
+        .sum();
+        self.thresholds
+            .partition_point(|&threshold| threshold as u64 <= material) as i32
+    }
+}
+
 pub struct PieceCount;
 
 impl BucketingScheme for PieceCount {
@@ -58,3 +111,144 @@ impl BucketingScheme for PieceCount {
         ((board.occupied().len() as i32 - 1) / 4).min(7)
     }
 }
+
+pub struct KingZone;
+
+impl BucketingScheme for KingZone {
+    const BUCKET_COUNT: usize = 9;
+
+    // Rank (back, second, other) x file zone (queenside, center, kingside) of the side to move king
+    fn bucket(board: &Board) -> i32 {
+        let stm = board.side_to_move();
+        let king = board.king(stm).relative_to(stm);
+        let rank = match king.rank() {
+            Rank::First => 0,
+            Rank::Second => 1,
+            _ => 2,
+        };
+        let zone = match king.file() {
+            File::A | File::B | File::C => 0,
+            File::D | File::E => 1,
+            _ => 2,
+        };
+        rank * 3 + zone
+    }
+}
+
+pub struct CastledKings;
+
+impl BucketingScheme for CastledKings {
+    const BUCKET_COUNT: usize = 4;
+
+    fn bucket(board: &Board) -> i32 {
+        let stm = board.side_to_move();
+        castled(board, stm) as i32 * 2 + castled(board, !stm) as i32
+    }
+}
+
+pub struct PawnCount;
+
+impl BucketingScheme for PawnCount {
+    const BUCKET_COUNT: usize = 8;
+
+    fn bucket(board: &Board) -> i32 {
+        (board.pieces(Piece::Pawn).len() as i32 / 2).min(7)
+    }
+}
+
+pub struct PassedPawns;
+
+impl BucketingScheme for PassedPawns {
+    const BUCKET_COUNT: usize = 4;
+
+    fn bucket(board: &Board) -> i32 {
+        let stm = board.side_to_move();
+        has_passed_pawn(board, stm) as i32 * 2 + has_passed_pawn(board, !stm) as i32
+    }
+}
+
+// A king counts as castled when it sits on its back rank outside of the center files
+fn castled(board: &Board, color: Color) -> bool {
+    let king = board.king(color).relative_to(color);
+    king.rank() == Rank::First && !matches!(king.file(), File::D | File::E | File::F)
+}
+
+fn has_passed_pawn(board: &Board, color: Color) -> bool {
+    let enemy_pawns = board.colored_pieces(!color, Piece::Pawn);
+    board.colored_pieces(color, Piece::Pawn).iter().any(|pawn| {
+        let pawn = pawn.relative_to(color);
+        enemy_pawns.iter().all(|enemy| {
+            let enemy = enemy.relative_to(color);
+            (enemy.file() as i32 - pawn.file() as i32).abs() > 1 || enemy.rank() <= pawn.rank()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_material_thresholds() {
+        let values = [1, 3, 3, 5, 8];
+        assert!(CustomMaterial::new(values, &[10, 10]).is_none());
+        assert!(CustomMaterial::new(values, &[0, 10]).is_none());
+        assert!(CustomMaterial::new(values, &[20, 10]).is_none());
+
+        // The starting position has 76 material by these values
+        let board = Board::default();
+        let custom = CustomMaterial::new(values, &[10, 40, 76]).unwrap();
+        assert_eq!(custom.bucket_count(), 4);
+        assert_eq!(custom.bucket(&board), 3);
+        let custom = CustomMaterial::new(values, &[77]).unwrap();
+        assert_eq!(custom.bucket(&board), 0);
+        let custom = CustomMaterial::new(values, &[]).unwrap();
+        assert_eq!(custom.bucket(&board), 0);
+    }
+
+    fn bucket<B: BucketingScheme>(fen: &str) -> i32 {
+        B::bucket(&fen.parse().unwrap())
+    }
+
+    #[test]
+    fn king_zones() {
+        assert_eq!(bucket::<KingZone>(&Board::default().to_string()), 1);
+        assert_eq!(bucket::<KingZone>("8/6k1/8/8/8/8/8/K7 b - - 0 1"), 5);
+        assert_eq!(bucket::<KingZone>("8/8/8/K7/8/8/8/7k w - - 0 1"), 6);
+    }
+
+    #[test]
+    fn castled_kings() {
+        assert_eq!(bucket::<CastledKings>(&Board::default().to_string()), 0);
+        assert_eq!(bucket::<CastledKings>("6k1/8/8/8/8/8/8/2K5 w - - 0 1"), 3);
+        assert_eq!(bucket::<CastledKings>("6k1/8/8/8/8/8/8/4K3 b - - 0 1"), 2);
+        assert_eq!(bucket::<CastledKings>("8/6k1/8/8/8/8/8/2K5 b - - 0 1"), 1);
+    }
+
+    #[test]
+    fn pawn_counts() {
+        assert_eq!(bucket::<PawnCount>(&Board::default().to_string()), 7);
+        assert_eq!(bucket::<PawnCount>("4k3/pp6/8/8/8/8/PPP5/4K3 w - - 0 1"), 2);
+        assert_eq!(bucket::<PawnCount>("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn passed_pawns() {
+        assert_eq!(bucket::<PassedPawns>(&Board::default().to_string()), 0);
+        assert_eq!(bucket::<PassedPawns>("4k3/8/8/8/8/3p4/8/4K3 w - - 0 1"), 1);
+        assert_eq!(
+            bucket::<PassedPawns>("4k3/8/3p4/8/3P4/8/8/4K3 w - - 0 1"),
+            0
+        );
+        // Each pawn has already passed the other, which black sees from the other side of the board
+        assert_eq!(
+            bucket::<PassedPawns>("4k3/8/3P4/8/3p4/8/8/4K3 b - - 0 1"),
+            3
+        );
+        assert_eq!(
+            bucket::<PassedPawns>("4k3/8/8/4p3/8/3P4/8/4K3 b - - 0 1"),
+            0
+        );
+        assert_eq!(bucket::<PassedPawns>("4k3/8/8/3Pp3/8/8/8/4K3 b - - 0 1"), 3);
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use crate::bucketing::{Bucketing, CustomMaterial};
+use crate::input_features::KingBucketTable;
+use crate::target::LambdaSchedule;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub enum ScorePerspective {
+    // The marlinformat convention
+    White,
+    SideToMove,
+}
+
+impl ScorePerspective {
+    pub const ALL: [ScorePerspective; 2] = [ScorePerspective::White, ScorePerspective::SideToMove];
+}
+
+#[derive(Clone, Debug)]
+pub struct LoaderConfig {
+    // Read the dataset through a memory map instead of copying it into a buffer
+    pub mmap: bool,
+    // Visit the chunks of a memory mapped dataset in a random order
+    pub shuffle_chunks: bool,
+    pub seed: u64,
+    // Passes over the dataset before this one, which give each pass its own random decisions
+    pub epoch: u64,
+    // Batches parsed together from one chunk of the dataset. Each of the `prefetch_depth + 1` chunk
+    // buffers reserves room for `max_features` features of every entry in these batches.
+    pub batches_per_chunk: u32,
+    // Chunks parsed ahead of the one being read
+    pub prefetch_depth: u32,
+    // Size of a dedicated parsing thread pool, 0 uses the global rayon pool
+    pub threads: u32,
+    // Measure the unpack and featurize stages, which costs a clock read per stage and position
+    pub collect_timings: bool,
+    // The perspective the dataset's eval and wdl are stored in
+    pub score_perspective: ScorePerspective,
+
+    // Bucket by this material specification instead of the reader's bucketing scheme
+    pub custom_material: Option<CustomMaterial>,
+    // Additional bucket outputs computed for every entry after the reader's bucketing scheme
+    pub extra_bucketing: Vec<Bucketing>,
+    // King buckets of the feature set, which must have as many as the table uses
+    pub king_buckets: Option<KingBucketTable>,
+
+    // Replace the WDL target of positions found in these syzygy tables
+    pub syzygy_path: Option<PathBuf>,
+    // 0 uses the largest tables available
+    pub syzygy_max_pieces: u32,
+    // Also replace the cp target with +-syzygy_win_cp or 0
+    pub syzygy_rescore_cp: bool,
+    pub syzygy_win_cp: f32,
+
+    // Mirror positions without castling rights across the d/e file with this probability
+    pub augment_mirror_probability: f64,
+    // Swap the colors of positions with this probability
+    pub augment_color_flip_probability: f64,
+    pub augment_seed: u64,
+
+    // Randomly skip positions, keeping each with this probability
+    pub keep_probability: f64,
+    // Skip positions before this fullmove number
+    pub min_fullmove: u16,
+
+    // Drop positions where the side to move is in check
+    pub filter_in_check: bool,
+    // Drop positions whose eval magnitude exceeds this
+    pub filter_max_eval: f32,
+    // Drop won (lost) positions whose eval is below (above) -margin (margin)
+    pub filter_wdl_margin: f32,
+    // Drop positions where the side to move has a capture available
+    pub filter_captures: bool,
+
+    // Scale the weight of positions before this fullmove number by weight_early
+    pub weight_early_fullmove: u16,
+    pub weight_early: f32,
+    // Scale the weight of won (lost) positions whose eval is below (above) -margin (margin)
+    pub weight_disagreement_margin: f32,
+    pub weight_disagreement: f32,
+    // Rescale weights so every primary bucket in a batch carries the same total weight
+    pub weight_balance_buckets: bool,
+
+    // Blend of eval and result written to the target buffer, see `Target`
+    pub target_scale: f32,
+    pub target_wdl_lambda: f32,
+    pub target_wdl_lambda_end: f32,
+    pub target_lambda_schedule: LambdaSchedule,
+    pub target_eval_limit: f32,
+}
+
+impl Default for LoaderConfig {
+    fn default() -> Self {
+        Self {
+            mmap: false,
+            shuffle_chunks: false,
+            seed: 0,
+            epoch: 0,
+            batches_per_chunk: 64,
+            prefetch_depth: 1,
+            threads: 0,
+            collect_timings: false,
+            score_perspective: ScorePerspective::White,
+            custom_material: None,
+            extra_bucketing: Vec::new(),
+            king_buckets: None,
+            syzygy_path: None,
+            syzygy_max_pieces: 0,
+            syzygy_rescore_cp: false,
+            syzygy_win_cp: 2000.0,
+            augment_mirror_probability: 0.0,
+            augment_color_flip_probability: 0.0,
+            augment_seed: 0,
+            keep_probability: 1.0,
+            min_fullmove: 0,
+            filter_in_check: false,
+            filter_max_eval: f32::INFINITY,
+            filter_wdl_margin: f32::INFINITY,
+            filter_captures: false,
+            weight_early_fullmove: 0,
+            weight_early: 1.0,
+            weight_disagreement_margin: f32::INFINITY,
+            weight_disagreement: 1.0,
+            weight_balance_buckets: false,
+            target_scale: 400.0,
+            target_wdl_lambda: 0.0,
+            target_wdl_lambda_end: 0.0,
+            target_lambda_schedule: LambdaSchedule::Constant,
+            target_eval_limit: 1000.0,
+        }
+    }
+}
@@ -1,62 +1,249 @@
 use std::io::{Seek, SeekFrom};
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
-use std::{fs::File, io::Read, path::Path};
+use std::sync::Arc;
+use std::time::Instant;
+use std::{fs::File, path::Path};
 
-use bytemuck::Zeroable;
 use cozy_chess::Color;
 use marlinformat::PackedBoard;
+use rand::prelude::*;
+use rand::rngs::StdRng;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
-use crate::batch::Batch;
-use crate::bucketing::*;
-use crate::input_features::*;
-
-const BUFFERED_BATCHES: usize = 64;
+use crate::augment::Augmentation;
+use crate::batch::{Batch, BatchLayout};
+use crate::board_source::BoardSource;
+use crate::bucketing::Bucketing;
+use crate::config::{LoaderConfig, ScorePerspective};
+use crate::filters::{FilterRule, Filters};
+use crate::input_features::{FeatureOptions, InputFeatureSet};
+use crate::registry::{BucketingSchemeType, InputFeatureSetType};
+use crate::stats::{elapsed_nanos, LoaderStage, LoaderStats};
+use crate::tablebase::Rescorer;
+use crate::target::Target;
+use crate::weights::Weights;
 
 pub struct BatchReader {
     recv: Receiver<Vec<Batch>>,
     reuse: SyncSender<Vec<Batch>>,
+    // The chunk `next_batch` reads from, empty before the first one is received
     batches: Vec<Batch>,
     index: usize,
+    // Shape of the chunks, for `new_chunk`
+    feature_format: InputFeatureSetType,
+    layout: BatchLayout,
+    bucket_outputs: usize,
+    batches_per_chunk: usize,
+    batch_size: usize,
     dataset_size: u64,
+    threads: usize,
+    stats: Arc<LoaderStats>,
+}
+
+pub struct LoaderContext {
+    score_perspective: ScorePerspective,
+    bucketing: Vec<Bucketing>,
+    features: FeatureOptions,
+    rescorer: Option<Rescorer>,
+    augmentation: Augmentation,
+    keep_probability: f64,
+    min_fullmove: u16,
+    filters: Filters,
+    weights: Weights,
+    target: Target,
+    seed: u64,
+    epoch: u64,
+    collect_timings: bool,
+    stats: Arc<LoaderStats>,
 }
 
 impl BatchReader {
     pub fn new(
         path: &Path,
         feature_format: InputFeatureSetType,
+        layout: BatchLayout,
         bucketing_scheme: BucketingSchemeType,
         batch_size: usize,
+        config: &LoaderConfig,
     ) -> std::io::Result<Self> {
         let mut file = File::open(path)?;
         let dataset_size = file.seek(SeekFrom::End(0))? / std::mem::size_of::<PackedBoard>() as u64;
         file.seek(SeekFrom::Start(0))?;
-        let (send, recv) = sync_channel(2);
-        let (reuse, reuse_recv) = sync_channel(2);
+        for &probability in &[
+            config.keep_probability,
+            config.augment_mirror_probability,
+            config.augment_color_flip_probability,
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "probabilities must be between 0 and 1",
+                ));
+            }
+        }
+        if batch_size == 0 || config.batches_per_chunk == 0 || config.prefetch_depth == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "batch size, batches per chunk and prefetch depth must be at least 1",
+            ));
+        }
+        if config.target_scale == 0.0 || config.target_eval_limit == 0.0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the target scale and eval limit must not be 0",
+            ));
+        }
+        if let Some(king_buckets) = &config.king_buckets {
+            if king_buckets.bucket_count() != feature_format.king_buckets() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "the king bucket table must use as many buckets as the feature set has",
+                ));
+            }
+        }
+        let pool = match config.threads {
+            0 => None,
+            threads => Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads as usize)
+                    .build()
+                    .map_err(std::io::Error::other)?,
+            ),
+        };
+        let threads = match &pool {
+            Some(pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        };
+        let batches_per_chunk = config.batches_per_chunk as usize;
+        let chunk_size = batch_size * batches_per_chunk;
+        let source = match config.mmap {
+            true => {
+                let shuffle = config
+                    .shuffle_chunks
+                    .then(|| loader_rng(config.seed, SHUFFLE_STREAM, config.epoch, 0));
+                BoardSource::mapped(&file, chunk_size, shuffle)?
+            }
+            false => BoardSource::buffered(file, chunk_size),
+        };
+        let rescorer = match &config.syzygy_path {
+            Some(path) => Some(Rescorer::new(
+                path,
+                config.syzygy_max_pieces,
+                config.syzygy_rescore_cp,
+                config.syzygy_win_cp,
+            )?),
+            None => None,
+        };
+        let stats = Arc::new(LoaderStats::default());
+        let primary_bucketing = match &config.custom_material {
+            Some(custom) => Bucketing::CustomMaterial(custom.clone()),
+            None => Bucketing::Scheme(bucketing_scheme),
+        };
+        let bucketing: Vec<_> = std::iter::once(primary_bucketing)
+            .chain(config.extra_bucketing.iter().cloned())
+            .collect();
+        let bucket_outputs = bucketing.len();
+        let ctx = LoaderContext {
+            score_perspective: config.score_perspective,
+            bucketing,
+            features: FeatureOptions {
+                king_buckets: config.king_buckets.clone(),
+            },
+            rescorer,
+            augmentation: Augmentation {
+                mirror_probability: config.augment_mirror_probability,
+                color_flip_probability: config.augment_color_flip_probability,
+                seed: config.augment_seed,
+            },
+            keep_probability: config.keep_probability,
+            min_fullmove: config.min_fullmove,
+            filters: Filters {
+                in_check: config.filter_in_check,
+                max_eval: config.filter_max_eval,
+                wdl_margin: config.filter_wdl_margin,
+                captures: config.filter_captures,
+            },
+            weights: Weights {
+                early_fullmove: config.weight_early_fullmove,
+                early_weight: config.weight_early,
+                disagreement_margin: config.weight_disagreement_margin,
+                disagreement_weight: config.weight_disagreement,
+                balance_buckets: config.weight_balance_buckets,
+            },
+            target: Target {
+                scale: config.target_scale,
+                wdl_lambda: config.target_wdl_lambda,
+                wdl_lambda_end: config.target_wdl_lambda_end,
+                schedule: config.target_lambda_schedule,
+                eval_limit: config.target_eval_limit,
+            },
+            seed: config.seed,
+            epoch: config.epoch,
+            collect_timings: config.collect_timings,
+            stats: stats.clone(),
+        };
+        // One buffer is held by the reader once reading starts, the rest are filled ahead of time
+        let prefetch_depth = config.prefetch_depth as usize;
+        let (send, recv) = sync_channel(prefetch_depth + 1);
+        let (reuse, reuse_recv) = sync_channel(prefetch_depth + 1);
         std::thread::spawn(move || {
             dataloader_thread(
                 send,
                 reuse_recv,
-                file,
+                source,
+                ctx,
+                pool,
                 feature_format,
-                bucketing_scheme,
                 batch_size,
             )
         });
-        let _ = reuse.send(batch_buffer(feature_format, batch_size));
-        Ok(Self {
+        let reader = Self {
             recv,
             reuse,
-            dataset_size,
-            batches: batch_buffer(feature_format, batch_size),
+            batches: Vec::new(),
             index: 0,
-        })
+            feature_format,
+            layout,
+            bucket_outputs,
+            batches_per_chunk,
+            batch_size,
+            dataset_size,
+            threads,
+            stats,
+        };
+        for _ in 0..prefetch_depth + 1 {
+            reader.recycle_chunk(reader.new_chunk());
+        }
+        Ok(reader)
+    }
+
+    // Bytes taken by the batch buffers of a reader with these settings, which are allocated up front
+    pub fn buffer_bytes(
+        feature_format: InputFeatureSetType,
+        layout: BatchLayout,
+        batch_size: usize,
+        config: &LoaderConfig,
+    ) -> usize {
+        let bucket_outputs = 1 + config.extra_bucketing.len();
+        let buffers = config.batches_per_chunk as usize * (config.prefetch_depth as usize + 1);
+        Batch::entry_bytes(feature_format.max_features(), layout, bucket_outputs)
+            * batch_size
+            * buffers
     }
 
     pub fn dataset_size(&self) -> u64 {
         self.dataset_size
     }
 
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn stats(&self) -> &LoaderStats {
+        &self.stats
+    }
+
     pub fn next_batch(&mut self) -> Option<&mut Batch> {
         loop {
             while self.index < self.batches.len() {
@@ -66,109 +253,77 @@ impl BatchReader {
                     return Some(&mut self.batches[i]);
                 }
             }
-            let _ = self.reuse.send(std::mem::take(&mut self.batches));
-            self.batches = self.recv.recv().ok()?;
+            let batches = std::mem::take(&mut self.batches);
+            if !batches.is_empty() {
+                self.recycle_chunk(batches);
+            }
+            self.batches = self.recv_chunk()?;
             self.index = 0;
         }
     }
+
+    // `recv_chunk`, `recycle_chunk` and `new_chunk` are for callers that keep batches around after
+    // reading the next ones, which `next_batch` overwrites. The loader holds `prefetch_depth + 1`
+    // chunks at first and waits for one to be recycled (or newly made) for every chunk received.
+    pub fn recv_chunk(&mut self) -> Option<Vec<Batch>> {
+        self.recv.recv().ok()
+    }
+
+    pub fn recycle_chunk(&self, batches: Vec<Batch>) {
+        let _ = self.reuse.send(batches);
+    }
+
+    pub fn new_chunk(&self) -> Vec<Batch> {
+        batch_buffer(
+            self.feature_format,
+            self.layout,
+            self.bucket_outputs,
+            self.batches_per_chunk,
+            self.batch_size,
+        )
+    }
 }
 
 fn dataloader_thread(
     send: SyncSender<Vec<Batch>>,
     reuse: Receiver<Vec<Batch>>,
-    mut file: File,
+    mut source: BoardSource,
+    ctx: LoaderContext,
+    pool: Option<ThreadPool>,
     feature_format: InputFeatureSetType,
-    bucketing_scheme: BucketingSchemeType,
     batch_size: usize,
 ) {
-    let mut board_buffer = vec![PackedBoard::zeroed(); batch_size * BUFFERED_BATCHES];
+    let process = feature_format.process_fn();
+    let mut batch_number = 0;
     for mut batches in reuse {
-        let buffer = bytemuck::cast_slice_mut(&mut board_buffer);
-        let mut bytes_read = 0;
-        loop {
-            match file.read(&mut buffer[bytes_read..]) {
-                Ok(0) => break,
-                Ok(some) => bytes_read += some,
-                Err(_) => return,
-            }
-        }
-        let elems = bytes_read / std::mem::size_of::<PackedBoard>();
-        if elems == 0 {
-            return;
-        }
-        let boards = &board_buffer[..elems];
+        let io_start = Instant::now();
+        let boards = match source.next_chunk() {
+            Some(boards) => boards,
+            None => return,
+        };
+        ctx.stats
+            .add_stage_nanos(LoaderStage::Io, io_start.elapsed().as_nanos() as u64);
 
         for batch in &mut batches {
             batch.clear();
         }
 
-        boards
-            .par_chunks(batch_size)
-            .zip(batches.par_iter_mut())
-            .for_each(|(boards, batch)| match feature_format {
-                InputFeatureSetType::Board768 => match bucketing_scheme {
-                    BucketingSchemeType::NoBucketing => {
-                        process::<Board768, NoBucketing>(batch, boards)
-                    }
-                    BucketingSchemeType::ModifiedMaterial => {
-                        process::<Board768, ModifiedMaterial>(batch, boards)
-                    }
-                    BucketingSchemeType::PieceCount => {
-                        process::<Board768, PieceCount>(batch, boards)
-                    }
-                },
-                InputFeatureSetType::HalfKp => match bucketing_scheme {
-                    BucketingSchemeType::NoBucketing => {
-                        process::<HalfKp, NoBucketing>(batch, boards)
-                    }
-                    BucketingSchemeType::ModifiedMaterial => {
-                        process::<HalfKp, ModifiedMaterial>(batch, boards)
-                    }
-                    BucketingSchemeType::PieceCount => process::<HalfKp, PieceCount>(batch, boards),
-                },
-                InputFeatureSetType::HalfKa => match bucketing_scheme {
-                    BucketingSchemeType::NoBucketing => {
-                        process::<HalfKa, NoBucketing>(batch, boards)
-                    }
-                    BucketingSchemeType::ModifiedMaterial => {
-                        process::<HalfKa, ModifiedMaterial>(batch, boards)
-                    }
-                    BucketingSchemeType::PieceCount => process::<HalfKa, PieceCount>(batch, boards),
-                },
-                InputFeatureSetType::Board768Cuda => match bucketing_scheme {
-                    BucketingSchemeType::NoBucketing => {
-                        process::<Board768Cuda, NoBucketing>(batch, boards)
-                    }
-                    BucketingSchemeType::ModifiedMaterial => {
-                        process::<Board768Cuda, ModifiedMaterial>(batch, boards)
-                    }
-                    BucketingSchemeType::PieceCount => {
-                        process::<Board768Cuda, PieceCount>(batch, boards)
-                    }
-                },
-                InputFeatureSetType::HalfKpCuda => match bucketing_scheme {
-                    BucketingSchemeType::NoBucketing => {
-                        process::<HalfKpCuda, NoBucketing>(batch, boards)
-                    }
-                    BucketingSchemeType::ModifiedMaterial => {
-                        process::<HalfKpCuda, ModifiedMaterial>(batch, boards)
-                    }
-                    BucketingSchemeType::PieceCount => {
-                        process::<HalfKpCuda, PieceCount>(batch, boards)
-                    }
-                },
-                InputFeatureSetType::HalfKaCuda => match bucketing_scheme {
-                    BucketingSchemeType::NoBucketing => {
-                        process::<HalfKaCuda, NoBucketing>(batch, boards)
-                    }
-                    BucketingSchemeType::ModifiedMaterial => {
-                        process::<HalfKaCuda, ModifiedMaterial>(batch, boards)
-                    }
-                    BucketingSchemeType::PieceCount => {
-                        process::<HalfKaCuda, PieceCount>(batch, boards)
-                    }
-                },
-            });
+        let parse_start = Instant::now();
+        let mut work = || {
+            boards
+                .par_chunks(batch_size)
+                .zip(batches.par_iter_mut().enumerate())
+                .for_each(|(boards, (i, batch))| {
+                    process(batch, boards, &ctx, batch_number + i as u64)
+                })
+        };
+        match &pool {
+            Some(pool) => pool.install(work),
+            None => work(),
+        }
+        ctx.stats
+            .add_stage_nanos(LoaderStage::Parse, parse_start.elapsed().as_nanos() as u64);
+        batch_number += batches.len() as u64;
 
         if send.send(batches).is_err() {
             break;
@@ -176,34 +331,429 @@ fn dataloader_thread(
     }
 }
 
-fn process<F: InputFeatureSet, B: BucketingScheme>(batch: &mut Batch, boards: &[PackedBoard]) {
+const SKIP_STREAM: u64 = 0;
+const AUGMENT_STREAM: u64 = 1;
+const SHUFFLE_STREAM: u64 = 2;
+
+// Every (seed, stream, epoch, batch) key gets its own ChaCha stream, so neither equal seeds for
+// different streams nor neighbouring seeds, epochs or batches replay each other's random numbers
+fn loader_rng(seed: u64, stream: u64, epoch: u64, batch_index: u64) -> StdRng {
+    let mut key = [0; 32];
+    for (bytes, value) in key
+        .chunks_exact_mut(8)
+        .zip([seed, stream, epoch, batch_index])
+    {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    StdRng::from_seed(key)
+}
+
+// Scores are made relative to the side to move right after unpacking, which is the perspective of the
+// `cp` and `wdl` seen by rescoring, filters, weights and targets, and of the batch buffers.
+pub fn process<F: InputFeatureSet>(
+    batch: &mut Batch,
+    boards: &[PackedBoard],
+    ctx: &LoaderContext,
+    batch_index: u64,
+) {
+    let busy_start = Instant::now();
+    let timing_start = || ctx.collect_timings.then(Instant::now);
+    let mut unpack_nanos = 0;
+    let mut featurize_nanos = 0;
+    let mut rng = loader_rng(ctx.seed, SKIP_STREAM, ctx.epoch, batch_index);
+    let mut augment_rng = loader_rng(
+        ctx.augmentation.seed,
+        AUGMENT_STREAM,
+        ctx.epoch,
+        batch_index,
+    );
+    let mut rescored = 0;
+    let mut filtered = [0; FilterRule::NUM];
     for packed in boards {
+        if ctx.keep_probability < 1.0 && !rng.gen_bool(ctx.keep_probability) {
+            continue;
+        }
         (|| {
-            let (board, cp, wdl, _) = packed.unpack()?;
+            let start = timing_start();
+            let unpacked = packed.unpack();
+            unpack_nanos += elapsed_nanos(start);
+            let (mut board, cp, wdl, _) = unpacked?;
+            if board.fullmove_number() < ctx.min_fullmove {
+                return None;
+            }
             let cp = cp as f32;
             let wdl = wdl as f32 / 2.0;
 
-            let (cp, wdl) = match board.side_to_move() {
-                Color::White => (cp, wdl),
-                Color::Black => (-cp, 1.0 - wdl),
+            let (mut cp, mut wdl) = match (ctx.score_perspective, board.side_to_move()) {
+                (ScorePerspective::White, Color::Black) => (-cp, 1.0 - wdl),
+                _ => (cp, wdl),
             };
 
-            let entry = batch.make_entry(cp, wdl, B::bucket(&board));
-            F::add_features(board, entry);
+            ctx.augmentation.apply(&mut board, &mut augment_rng);
+
+            if let Some(rescorer) = &ctx.rescorer {
+                if rescorer.rescore(&board, &mut cp, &mut wdl) {
+                    rescored += 1;
+                }
+            }
+
+            if let Some(rule) = ctx.filters.rejects(&board, cp, wdl) {
+                filtered[rule as usize] += 1;
+                return None;
+            }
+
+            let start = timing_start();
+            let buckets = ctx
+                .bucketing
+                .iter()
+                .map(|bucketing| bucketing.bucket(&board));
+            let target = ctx.target.target(&board, cp, wdl);
+            let weight = ctx.weights.weight(&board, cp, wdl);
+            let entry = batch.make_entry(cp, wdl, target, weight, buckets);
+            F::add_features(board, entry, &ctx.features);
+            featurize_nanos += elapsed_nanos(start);
 
             Some(())
         })();
     }
+    if ctx.weights.balance_buckets {
+        batch.balance_bucket_weights();
+    }
+    ctx.stats.add_rescored(rescored);
+    ctx.stats.add_filtered(&filtered);
+    ctx.stats.add_stage_nanos(LoaderStage::Unpack, unpack_nanos);
+    ctx.stats
+        .add_stage_nanos(LoaderStage::Featurize, featurize_nanos);
+    ctx.stats
+        .add_stage_nanos(LoaderStage::Busy, busy_start.elapsed().as_nanos() as u64);
 }
 
-fn batch_buffer(feature_format: InputFeatureSetType, batch_size: usize) -> Vec<Batch> {
+fn batch_buffer(
+    feature_format: InputFeatureSetType,
+    layout: BatchLayout,
+    bucket_outputs: usize,
+    batches_per_chunk: usize,
+    batch_size: usize,
+) -> Vec<Batch> {
     let mut v = vec![];
-    v.resize_with(BUFFERED_BATCHES, || {
+    v.resize_with(batches_per_chunk, || {
         Batch::new(
             batch_size,
             feature_format.max_features(),
-            feature_format.indices_per_feature(),
+            layout,
+            bucket_outputs,
         )
     });
     v
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use cozy_chess::Board;
+
+    use super::*;
+    use crate::input_features::KingBucketTable;
+
+    fn random_positions(count: usize) -> Vec<PackedBoard> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut board = Board::default();
+        let mut positions = vec![];
+        while positions.len() < count {
+            let mut moves = vec![];
+            board.generate_moves(|piece_moves| {
+                moves.extend(piece_moves);
+                false
+            });
+            match moves.choose(&mut rng) {
+                Some(&mv) => board.play_unchecked(mv),
+                None => board = Board::default(),
+            }
+            // Evals number the positions
+            positions.push(PackedBoard::pack(&board, positions.len() as i16, 1, 0));
+        }
+        positions
+    }
+
+    fn dataset(name: &str, positions: &[PackedBoard]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("parse-{}-{}.bin", name, std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(bytemuck::cast_slice(positions))
+            .unwrap();
+        path
+    }
+
+    fn board_768_reader(path: &Path, config: &LoaderConfig) -> BatchReader {
+        BatchReader::new(
+            path,
+            InputFeatureSetType::from_name(b"Board768").unwrap(),
+            BatchLayout::Sparse,
+            BucketingSchemeType::from_name(b"NoBucketing").unwrap(),
+            64,
+            config,
+        )
+        .unwrap()
+    }
+
+    // The numbers of the positions read, in order
+    fn read_positions(path: &Path, config: &LoaderConfig) -> Vec<u32> {
+        let mut reader = board_768_reader(path, config);
+        let mut positions = vec![];
+        while let Some(batch) = reader.next_batch() {
+            let cp = unsafe { std::slice::from_raw_parts(batch.cp_ptr(), batch.len()) };
+            positions.extend(cp.iter().map(|cp| cp.abs() as u32));
+        }
+        positions
+    }
+
+    #[test]
+    fn features_within_input_range() {
+        let path = dataset("features", &random_positions(4096));
+
+        for feature_set in InputFeatureSetType::all() {
+            for &layout in &[BatchLayout::Sparse, BatchLayout::Cuda, BatchLayout::Csr] {
+                let num_inputs = feature_set.num_inputs() as i64;
+                let indices_per_feature = layout.indices_per_feature();
+                let mut reader = BatchReader::new(
+                    &path,
+                    feature_set,
+                    layout,
+                    BucketingSchemeType::from_name(b"NoBucketing").unwrap(),
+                    256,
+                    &LoaderConfig::default(),
+                )
+                .unwrap();
+
+                while let Some(batch) = reader.next_batch() {
+                    if layout == BatchLayout::Csr {
+                        let offsets =
+                            unsafe { std::slice::from_raw_parts(batch.offsets_ptr(), batch.len()) };
+                        assert!(offsets.windows(2).all(|pair| pair[0] <= pair[1]));
+                        if let Some(&last) = offsets.last() {
+                            assert!(last as usize <= batch.total_features());
+                        }
+                    }
+
+                    let len = batch.total_features() * indices_per_feature;
+                    for &ptr in &[
+                        batch.stm_feature_buffer_ptr(),
+                        batch.nstm_feature_buffer_ptr(),
+                    ] {
+                        let features = unsafe { std::slice::from_raw_parts(ptr, len) };
+                        for &feature in features
+                            .iter()
+                            .skip(indices_per_feature - 1)
+                            .step_by(indices_per_feature)
+                        {
+                            let padding = layout == BatchLayout::Cuda && feature == -1;
+                            assert!(
+                                (0..num_inputs).contains(&feature) || padding,
+                                "{:?} ({:?}) produced feature {} outside of 0..{}",
+                                feature_set.name(),
+                                layout,
+                                feature,
+                                num_inputs,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn epochs_skip_different_positions() {
+        let path = dataset("epochs", &random_positions(4096));
+        let kept = |epoch| {
+            let config = LoaderConfig {
+                keep_probability: 0.5,
+                epoch,
+                ..LoaderConfig::default()
+            };
+            read_positions(&path, &config)
+        };
+        assert_eq!(kept(0), kept(0));
+        assert_ne!(kept(0), kept(1));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn epochs_augment_differently() {
+        let path = dataset("augment", &random_positions(4096));
+        let features = |epoch| {
+            let config = LoaderConfig {
+                augment_color_flip_probability: 0.5,
+                epoch,
+                ..LoaderConfig::default()
+            };
+            let mut reader = board_768_reader(&path, &config);
+            let mut features = vec![];
+            while let Some(batch) = reader.next_batch() {
+                let len = batch.total_features() * batch.indices_per_feature();
+                features.extend_from_slice(unsafe {
+                    std::slice::from_raw_parts(batch.stm_feature_buffer_ptr(), len)
+                });
+            }
+            features
+        };
+        assert_eq!(features(0), features(0));
+        assert_ne!(features(0), features(1));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn mmap_reads_like_buffered() {
+        // Ends in a partial chunk
+        let path = dataset("mmap", &random_positions(5000));
+        let buffered = read_positions(&path, &LoaderConfig::default());
+        assert_eq!(buffered, (0..5000).collect::<Vec<_>>());
+        let mapped = LoaderConfig {
+            mmap: true,
+            ..LoaderConfig::default()
+        };
+        assert_eq!(read_positions(&path, &mapped), buffered);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn shuffled_chunks_cover_the_dataset() {
+        let path = dataset("shuffle", &random_positions(5000));
+        let shuffled = |epoch| {
+            let config = LoaderConfig {
+                mmap: true,
+                shuffle_chunks: true,
+                batches_per_chunk: 1,
+                epoch,
+                ..LoaderConfig::default()
+            };
+            read_positions(&path, &config)
+        };
+        let first = shuffled(0);
+        assert_ne!(first, (0..5000).collect::<Vec<_>>());
+        assert_eq!(first, shuffled(0));
+        assert_ne!(first, shuffled(1));
+        let mut sorted = first;
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..5000).collect::<Vec<_>>());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn counts_filtered_positions() {
+        let position = |fen: &str, cp, wdl| PackedBoard::pack(&fen.parse().unwrap(), cp, wdl, 0);
+        let quiet = "4k3/8/8/8/8/8/8/4K2R w - - 0 1";
+        // Rejected by each rule in turn, then kept, one more position per case
+        let cases = [
+            ("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1", 0, 1),
+            (quiet, 2000, 2),
+            (quiet, -300, 2),
+            ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", 0, 1),
+            (quiet, 50, 1),
+        ];
+        let mut positions = vec![];
+        for (count, &(fen, cp, wdl)) in (1..).zip(&cases) {
+            positions.extend(vec![position(fen, cp, wdl); count]);
+        }
+        let path = dataset("filters", &positions);
+        let config = LoaderConfig {
+            filter_in_check: true,
+            filter_max_eval: 1000.0,
+            filter_wdl_margin: 200.0,
+            filter_captures: true,
+            ..LoaderConfig::default()
+        };
+
+        let mut reader = board_768_reader(&path, &config);
+        let mut kept = 0;
+        while let Some(batch) = reader.next_batch() {
+            kept += batch.len();
+        }
+        assert_eq!(kept, 5);
+        for (&rule, count) in FilterRule::ALL.iter().zip(1..) {
+            assert_eq!(reader.stats().filtered(rule), count);
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rejects_empty_batches() {
+        let path = dataset("empty-batches", &random_positions(64));
+        let reader = BatchReader::new(
+            &path,
+            InputFeatureSetType::from_name(b"Board768").unwrap(),
+            BatchLayout::Sparse,
+            BucketingSchemeType::from_name(b"NoBucketing").unwrap(),
+            0,
+            &LoaderConfig::default(),
+        );
+        assert!(reader.is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rejects_zero_target_divisors() {
+        let path = dataset("target-divisors", &random_positions(64));
+        for config in &[
+            LoaderConfig {
+                target_scale: 0.0,
+                ..LoaderConfig::default()
+            },
+            LoaderConfig {
+                target_eval_limit: 0.0,
+                ..LoaderConfig::default()
+            },
+        ] {
+            let reader = BatchReader::new(
+                &path,
+                InputFeatureSetType::from_name(b"Board768").unwrap(),
+                BatchLayout::Sparse,
+                BucketingSchemeType::from_name(b"NoBucketing").unwrap(),
+                64,
+                config,
+            );
+            assert!(reader.is_err());
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn king_bucket_table_matches_feature_set() {
+        let path = dataset("king-buckets", &random_positions(64));
+        let reader = |feature_set: &[u8], table| {
+            let config = LoaderConfig {
+                king_buckets: KingBucketTable::new(table),
+                ..LoaderConfig::default()
+            };
+            BatchReader::new(
+                &path,
+                InputFeatureSetType::from_name(feature_set).unwrap(),
+                BatchLayout::Sparse,
+                BucketingSchemeType::from_name(b"NoBucketing").unwrap(),
+                64,
+                &config,
+            )
+        };
+        let mut two_buckets = [0; 32];
+        two_buckets[31] = 1;
+        let mut four_buckets = [3; 32];
+        four_buckets[..3].copy_from_slice(&[0, 1, 2]);
+        assert!(reader(b"HalfKaMirrored4", four_buckets).is_ok());
+        assert!(reader(b"HalfKaMirrored4", two_buckets).is_err());
+        assert!(reader(b"HalfKaMirrored16", four_buckets).is_err());
+        assert!(reader(b"Board768", two_buckets).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,97 @@
+use cozy_chess::{Board, Piece, Rank, Square};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub enum FilterRule {
+    InCheck,
+    Eval,
+    WdlDisagreement,
+    Capture,
+}
+
+impl FilterRule {
+    pub const NUM: usize = 4;
+    pub const ALL: [FilterRule; FilterRule::NUM] = [
+        FilterRule::InCheck,
+        FilterRule::Eval,
+        FilterRule::WdlDisagreement,
+        FilterRule::Capture,
+    ];
+}
+
+pub struct Filters {
+    pub in_check: bool,
+    pub max_eval: f32,
+    pub wdl_margin: f32,
+    pub captures: bool,
+}
+
+impl Filters {
+    // Returns the first rule rejecting the position
+    pub fn rejects(&self, board: &Board, cp: f32, wdl: f32) -> Option<FilterRule> {
+        if self.in_check && !board.checkers().is_empty() {
+            return Some(FilterRule::InCheck);
+        }
+        if cp.abs() > self.max_eval {
+            return Some(FilterRule::Eval);
+        }
+        if disagrees(cp, wdl, self.wdl_margin) {
+            return Some(FilterRule::WdlDisagreement);
+        }
+        if self.captures && has_capture(board) {
+            return Some(FilterRule::Capture);
+        }
+        None
+    }
+}
+
+// Whether the position was won (lost) despite an eval below (above) -margin (margin)
+pub fn disagrees(cp: f32, wdl: f32, margin: f32) -> bool {
+    wdl == 1.0 && cp < -margin || wdl == 0.0 && cp > margin
+}
+
+fn has_capture(board: &Board) -> bool {
+    let stm = board.side_to_move();
+    let targets = board.colors(!stm);
+    let ep_square = board
+        .en_passant()
+        .map(|file| Square::new(file, Rank::Sixth.relative_to(stm)));
+    board.generate_moves(|moves| {
+        let mut captures = moves.to & targets;
+        if moves.piece == Piece::Pawn {
+            if let Some(ep_square) = ep_square {
+                captures |= ep_square.bitboard() & moves.to;
+            }
+        }
+        !captures.is_empty()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_capture_fen(fen: &str) -> bool {
+        has_capture(&fen.parse().unwrap())
+    }
+
+    #[test]
+    fn captures() {
+        assert!(has_capture_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1"));
+        assert!(has_capture_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2"));
+        assert!(has_capture_fen("4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 1"));
+        assert!(!has_capture_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 2"));
+        assert!(!has_capture_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(!has_capture_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1"));
+    }
+
+    #[test]
+    fn disagreement_margin() {
+        assert!(!disagrees(-100.0, 1.0, 100.0));
+        assert!(disagrees(-101.0, 1.0, 100.0));
+        assert!(!disagrees(100.0, 0.0, 100.0));
+        assert!(disagrees(101.0, 0.0, 100.0));
+        assert!(!disagrees(-1000.0, 0.5, 100.0));
+        assert!(!disagrees(1000.0, 1.0, 100.0));
+    }
+}
@@ -2,38 +2,15 @@ use cozy_chess::{Board, Color, Piece, Square};
 
 use crate::batch::EntryFeatureWriter;
 
-use super::InputFeatureSet;
+use super::{FeatureOptions, InputFeatureSet};
 
 pub struct Board768;
 
-pub struct Board768Cuda;
-
 impl InputFeatureSet for Board768 {
     const MAX_FEATURES: usize = 32;
-    const INDICES_PER_FEATURE: usize = 2;
-
-    fn add_features(board: Board, entry: EntryFeatureWriter) {
-        let mut sparse_entry = entry.sparse();
-        let stm = board.side_to_move();
-
-        for &color in &Color::ALL {
-            for &piece in &Piece::ALL {
-                for square in board.pieces(piece) & board.colors(color) {
-                    let stm_feature = feature(stm, color, piece, square);
-                    let nstm_feature = feature(!stm, color, piece, square);
-                    sparse_entry.add_feature(stm_feature as i64, nstm_feature as i64);
-                }
-            }
-        }
-    }
-}
-
-impl InputFeatureSet for Board768Cuda {
-    const MAX_FEATURES: usize = 32;
-    const INDICES_PER_FEATURE: usize = 1;
+    const NUM_INPUTS: usize = Color::NUM * Piece::NUM * Square::NUM;
 
-    fn add_features(board: Board, entry: EntryFeatureWriter) {
-        let mut cuda_entry = entry.cuda();
+    fn add_features(board: Board, mut entry: EntryFeatureWriter, _options: &FeatureOptions) {
         let stm = board.side_to_move();
 
         for &color in &Color::ALL {
@@ -41,7 +18,7 @@ impl InputFeatureSet for Board768Cuda {
                 for square in board.pieces(piece) & board.colors(color) {
                     let stm_feature = feature(stm, color, piece, square);
                     let nstm_feature = feature(!stm, color, piece, square);
-                    cuda_entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                    entry.add_feature(stm_feature as i64, nstm_feature as i64);
                 }
             }
         }
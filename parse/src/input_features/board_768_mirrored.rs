@@ -0,0 +1,90 @@
+use cozy_chess::{Board, Color, File, Piece, Square};
+
+use crate::batch::EntryFeatureWriter;
+
+use super::{FeatureOptions, InputFeatureSet};
+
+pub struct Board768Mirrored;
+
+impl InputFeatureSet for Board768Mirrored {
+    const MAX_FEATURES: usize = 32;
+    const NUM_INPUTS: usize = Color::NUM * Piece::NUM * Square::NUM;
+
+    fn add_features(board: Board, mut entry: EntryFeatureWriter, _options: &FeatureOptions) {
+        let stm = board.side_to_move();
+
+        let stm_mirror = board.king(stm).file() >= File::E;
+        let nstm_mirror = board.king(!stm).file() >= File::E;
+        entry.set_mirrored(stm_mirror, nstm_mirror);
+
+        for &color in &Color::ALL {
+            for &piece in &Piece::ALL {
+                for square in board.pieces(piece) & board.colors(color) {
+                    let stm_feature = feature(stm, stm_mirror, color, piece, square);
+                    let nstm_feature = feature(!stm, nstm_mirror, color, piece, square);
+                    entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                }
+            }
+        }
+    }
+}
+
+// Mirroring puts the perspective king on files a-d
+fn feature(perspective: Color, mirror: bool, color: Color, piece: Piece, square: Square) -> usize {
+    let (square, color) = match perspective {
+        Color::White => (square, color),
+        Color::Black => (square.flip_rank(), !color),
+    };
+    let square = match mirror {
+        true => square.flip_file(),
+        false => square,
+    };
+    let mut index = 0;
+    index = index * Color::NUM + color as usize;
+    index = index * Piece::NUM + piece as usize;
+    index = index * Square::NUM + square as usize;
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{Batch, BatchLayout};
+
+    #[test]
+    fn mirrors_kings_on_files_e_to_h() {
+        let board: Board = "1k6/8/8/8/8/8/7P/6K1 w - - 0 1".parse().unwrap();
+        let mut batch = Batch::new(1, Board768Mirrored::MAX_FEATURES, BatchLayout::Csr, 1);
+        let entry = batch.make_entry(0.0, 0.5, 0.5, 1.0, std::iter::once(0));
+        Board768Mirrored::add_features(board, entry, &FeatureOptions::default());
+
+        let len = batch.total_features();
+        let stm = unsafe { std::slice::from_raw_parts(batch.stm_feature_buffer_ptr(), len) };
+        let nstm = unsafe { std::slice::from_raw_parts(batch.nstm_feature_buffer_ptr(), len) };
+        let stm_mirrored = unsafe { *batch.stm_mirrored_ptr() };
+        let nstm_mirrored = unsafe { *batch.nstm_mirrored_ptr() };
+
+        // The white king on g1 moves the white perspective over to b1, the black king on b8 stays
+        let index = |color: Color, piece: Piece, square: Square| {
+            ((color as usize * Piece::NUM + piece as usize) * Square::NUM + square as usize) as i64
+        };
+        assert!(stm_mirrored);
+        assert!(!nstm_mirrored);
+        assert_eq!(
+            stm,
+            [
+                index(Color::White, Piece::Pawn, Square::A2),
+                index(Color::White, Piece::King, Square::B1),
+                index(Color::Black, Piece::King, Square::G8),
+            ]
+        );
+        assert_eq!(
+            nstm,
+            [
+                index(Color::Black, Piece::Pawn, Square::H7),
+                index(Color::Black, Piece::King, Square::G8),
+                index(Color::White, Piece::King, Square::B1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, Color, Piece, Square,
+};
+
+use crate::batch::EntryFeatureWriter;
+
+use super::{FeatureOptions, InputFeatureSet};
+
+pub struct Board768Threats;
+
+// A piece is either attacked by an enemy piece type or defended by a friendly piece type
+const ATTACKED: usize = 0;
+const DEFENDED: usize = 1;
+const RELATIONS: usize = 2;
+
+const PIECE_SQUARE_INPUTS: usize = Color::NUM * Piece::NUM * Square::NUM;
+
+// Each of at most 32 pieces can be attacked and defended by every piece type
+const MAX_THREATS: usize = 32 * RELATIONS * Piece::NUM;
+const MAX_FEATURES: usize = 32 + MAX_THREATS;
+const NUM_INPUTS: usize = PIECE_SQUARE_INPUTS * (1 + RELATIONS * Piece::NUM);
+
+impl InputFeatureSet for Board768Threats {
+    const MAX_FEATURES: usize = MAX_FEATURES;
+    const NUM_INPUTS: usize = NUM_INPUTS;
+
+    fn add_features(board: Board, mut entry: EntryFeatureWriter, _options: &FeatureOptions) {
+        let stm = board.side_to_move();
+        let attacks = attacks(&board);
+
+        for &color in &Color::ALL {
+            for &piece in &Piece::ALL {
+                for square in board.pieces(piece) & board.colors(color) {
+                    let stm_feature = feature(stm, color, piece, square);
+                    let nstm_feature = feature(!stm, color, piece, square);
+                    entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                }
+            }
+        }
+
+        for &color in &Color::ALL {
+            for &piece in &Piece::ALL {
+                for square in board.pieces(piece) & board.colors(color) {
+                    for_each_threat(&attacks, color, square, |relation, attacker| {
+                        let stm_feature =
+                            threat_feature(stm, relation, attacker, color, piece, square);
+                        let nstm_feature =
+                            threat_feature(!stm, relation, attacker, color, piece, square);
+                        entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                    });
+                }
+            }
+        }
+    }
+}
+
+// The squares attacked by each piece type of each color
+fn attacks(board: &Board) -> [[BitBoard; Piece::NUM]; Color::NUM] {
+    let occupied = board.occupied();
+    let mut attacks = [[BitBoard::EMPTY; Piece::NUM]; Color::NUM];
+    for &color in &Color::ALL {
+        for &piece in &Piece::ALL {
+            for square in board.pieces(piece) & board.colors(color) {
+                attacks[color as usize][piece as usize] |= match piece {
+                    Piece::Pawn => get_pawn_attacks(square, color),
+                    Piece::Knight => get_knight_moves(square),
+                    Piece::Bishop => get_bishop_moves(square, occupied),
+                    Piece::Rook => get_rook_moves(square, occupied),
+                    Piece::Queen => {
+                        get_bishop_moves(square, occupied) | get_rook_moves(square, occupied)
+                    }
+                    Piece::King => get_king_moves(square),
+                };
+            }
+        }
+    }
+    attacks
+}
+
+fn for_each_threat(
+    attacks: &[[BitBoard; Piece::NUM]; Color::NUM],
+    color: Color,
+    square: Square,
+    mut f: impl FnMut(usize, Piece),
+) {
+    for &attacker in &Piece::ALL {
+        if attacks[!color as usize][attacker as usize].has(square) {
+            f(ATTACKED, attacker);
+        }
+        if attacks[color as usize][attacker as usize].has(square) {
+            f(DEFENDED, attacker);
+        }
+    }
+}
+
+fn feature(perspective: Color, color: Color, piece: Piece, square: Square) -> usize {
+    let (square, color) = match perspective {
+        Color::White => (square, color),
+        Color::Black => (square.flip_rank(), !color),
+    };
+    let mut index = 0;
+    index = index * Color::NUM + color as usize;
+    index = index * Piece::NUM + piece as usize;
+    index = index * Square::NUM + square as usize;
+    index
+}
+
+fn threat_feature(
+    perspective: Color,
+    relation: usize,
+    attacker: Piece,
+    color: Color,
+    piece: Piece,
+    square: Square,
+) -> usize {
+    let mut index = 0;
+    index = index * RELATIONS + relation;
+    index = index * Piece::NUM + attacker as usize;
+    index = index * PIECE_SQUARE_INPUTS + feature(perspective, color, piece, square);
+    // Threat features are indexed after the piece-square features
+    PIECE_SQUARE_INPUTS + index
+}
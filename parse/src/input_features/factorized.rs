@@ -0,0 +1,35 @@
+use std::marker::PhantomData;
+
+use cozy_chess::Board;
+
+use crate::batch::EntryFeatureWriter;
+
+use super::{
+    FeatureOptions, HalfKa, HalfKaMirrored16, HalfKaMirrored4, HalfKaV2, HalfKp, InputFeatureSet,
+};
+
+// A king relative feature set whose features are laid out as `king * FACTOR_INPUTS + piece_square`
+pub trait Factorize: InputFeatureSet {
+    const FACTOR_INPUTS: usize;
+}
+
+// Emits the king independent virtual feature after every feature of `F`. Virtual features are
+// indexed after the features of `F`.
+pub struct Factorized<F>(PhantomData<F>);
+
+impl<F: Factorize> InputFeatureSet for Factorized<F> {
+    const MAX_FEATURES: usize = F::MAX_FEATURES * 2;
+    const NUM_INPUTS: usize = F::NUM_INPUTS + F::FACTOR_INPUTS;
+    const KING_BUCKETS: usize = F::KING_BUCKETS;
+
+    fn add_features(board: Board, entry: EntryFeatureWriter, options: &FeatureOptions) {
+        let entry = entry.factorized(F::NUM_INPUTS, F::FACTOR_INPUTS);
+        F::add_features(board, entry, options);
+    }
+}
+
+pub type HalfKpFactorized = Factorized<HalfKp>;
+pub type HalfKaFactorized = Factorized<HalfKa>;
+pub type HalfKaMirrored4Factorized = Factorized<HalfKaMirrored4>;
+pub type HalfKaMirrored16Factorized = Factorized<HalfKaMirrored16>;
+pub type HalfKaV2Factorized = Factorized<HalfKaV2>;
@@ -2,17 +2,15 @@ use cozy_chess::{Board, Color, Piece, Square};
 
 use crate::batch::EntryFeatureWriter;
 
-use super::InputFeatureSet;
+use super::{Factorize, FeatureOptions, InputFeatureSet};
 
 pub struct HalfKa;
-pub struct HalfKaCuda;
 
 impl InputFeatureSet for HalfKa {
     const MAX_FEATURES: usize = 32;
-    const INDICES_PER_FEATURE: usize = 2;
+    const NUM_INPUTS: usize = Square::NUM * Color::NUM * Piece::NUM * Square::NUM;
 
-    fn add_features(board: Board, entry: EntryFeatureWriter) {
-        let mut sparse_entry = entry.sparse();
+    fn add_features(board: Board, mut entry: EntryFeatureWriter, _options: &FeatureOptions) {
         let stm = board.side_to_move();
 
         let stm_king = board.king(stm);
@@ -23,34 +21,15 @@ impl InputFeatureSet for HalfKa {
                 for square in board.pieces(piece) & board.colors(color) {
                     let stm_feature = feature(stm, stm_king, color, piece, square);
                     let nstm_feature = feature(!stm, nstm_king, color, piece, square);
-                    sparse_entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                    entry.add_feature(stm_feature as i64, nstm_feature as i64);
                 }
             }
         }
     }
 }
 
-impl InputFeatureSet for HalfKaCuda {
-    const MAX_FEATURES: usize = 32;
-    const INDICES_PER_FEATURE: usize = 1;
-
-    fn add_features(board: Board, entry: EntryFeatureWriter) {
-        let mut cuda_entry = entry.cuda();
-        let stm = board.side_to_move();
-
-        let stm_king = board.king(stm);
-        let nstm_king = board.king(!stm);
-
-        for &color in &Color::ALL {
-            for &piece in &Piece::ALL {
-                for square in board.pieces(piece) & board.colors(color) {
-                    let stm_feature = feature(stm, stm_king, color, piece, square);
-                    let nstm_feature = feature(!stm, nstm_king, color, piece, square);
-                    cuda_entry.add_feature(stm_feature as i64, nstm_feature as i64);
-                }
-            }
-        }
-    }
+impl Factorize for HalfKa {
+    const FACTOR_INPUTS: usize = Color::NUM * Piece::NUM * Square::NUM;
 }
 
 fn feature(perspective: Color, king: Square, color: Color, piece: Piece, square: Square) -> usize {
@@ -0,0 +1,157 @@
+use std::marker::PhantomData;
+
+use cozy_chess::{Board, Color, File, Piece, Square};
+
+use crate::batch::EntryFeatureWriter;
+
+use super::{Factorize, FeatureOptions, InputFeatureSet};
+
+pub trait KingBuckets {
+    const COUNT: usize;
+    // Indexed by `rank * 4 + file` of the perspective king after mirroring it onto files a-d
+    const TABLE: [usize; 32];
+}
+
+pub struct KingBuckets4;
+
+impl KingBuckets for KingBuckets4 {
+    const COUNT: usize = 4;
+    #[rustfmt::skip]
+    const TABLE: [usize; 32] = [
+        0, 0, 1, 1,
+        2, 2, 2, 2,
+        3, 3, 3, 3,
+        3, 3, 3, 3,
+        3, 3, 3, 3,
+        3, 3, 3, 3,
+        3, 3, 3, 3,
+        3, 3, 3, 3,
+    ];
+}
+
+pub struct KingBuckets16;
+
+impl KingBuckets for KingBuckets16 {
+    const COUNT: usize = 16;
+    #[rustfmt::skip]
+    const TABLE: [usize; 32] = [
+         0,  1,  2,  3,
+         4,  5,  6,  7,
+         8,  8,  9,  9,
+        10, 10, 11, 11,
+        12, 12, 13, 13,
+        12, 12, 13, 13,
+        14, 14, 15, 15,
+        14, 14, 15, 15,
+    ];
+}
+
+// A king bucket table configured at runtime, replacing the table of the feature set it is used with
+#[derive(Clone, Debug)]
+pub struct KingBucketTable {
+    table: [usize; 32],
+    count: usize,
+}
+
+impl KingBucketTable {
+    // Every bucket from 0 to the largest one must be used by at least one square
+    pub fn new(table: [usize; 32]) -> Option<Self> {
+        let count = table.iter().max()? + 1;
+        if (0..count).any(|bucket| !table.contains(&bucket)) {
+            return None;
+        }
+        Some(Self { table, count })
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.count
+    }
+}
+
+pub struct HalfKaMirrored<K>(PhantomData<K>);
+
+pub type HalfKaMirrored4 = HalfKaMirrored<KingBuckets4>;
+pub type HalfKaMirrored16 = HalfKaMirrored<KingBuckets16>;
+
+impl<K: KingBuckets> InputFeatureSet for HalfKaMirrored<K> {
+    const MAX_FEATURES: usize = 32;
+    const NUM_INPUTS: usize = K::COUNT * Color::NUM * Piece::NUM * Square::NUM;
+    const KING_BUCKETS: usize = K::COUNT;
+
+    fn add_features(board: Board, mut entry: EntryFeatureWriter, options: &FeatureOptions) {
+        let table = match &options.king_buckets {
+            Some(king_buckets) => &king_buckets.table,
+            None => &K::TABLE,
+        };
+        let stm = board.side_to_move();
+
+        let stm_king = board.king(stm);
+        let nstm_king = board.king(!stm);
+        entry.set_mirrored(stm_king.file() >= File::E, nstm_king.file() >= File::E);
+
+        for &color in &Color::ALL {
+            for &piece in &Piece::ALL {
+                for square in board.pieces(piece) & board.colors(color) {
+                    let stm_feature = feature::<K>(table, stm, stm_king, color, piece, square);
+                    let nstm_feature = feature::<K>(table, !stm, nstm_king, color, piece, square);
+                    entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                }
+            }
+        }
+    }
+}
+
+impl<K: KingBuckets> Factorize for HalfKaMirrored<K> {
+    const FACTOR_INPUTS: usize = Color::NUM * Piece::NUM * Square::NUM;
+}
+
+fn feature<K: KingBuckets>(
+    table: &[usize; 32],
+    perspective: Color,
+    king: Square,
+    color: Color,
+    piece: Piece,
+    square: Square,
+) -> usize {
+    let (king, square, color) = match perspective {
+        Color::White => (king, square, color),
+        Color::Black => (king.flip_rank(), square.flip_rank(), !color),
+    };
+    let (king, square) = match king.file() >= File::E {
+        true => (king.flip_file(), square.flip_file()),
+        false => (king, square),
+    };
+    let bucket = table[king.rank() as usize * 4 + king.file() as usize];
+    let mut index = 0;
+    index = index * K::COUNT + bucket;
+    index = index * Color::NUM + color as usize;
+    index = index * Piece::NUM + piece as usize;
+    index = index * Square::NUM + square as usize;
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn king_bucket_table_validation() {
+        assert_eq!(
+            KingBucketTable::new(KingBuckets4::TABLE)
+                .unwrap()
+                .bucket_count(),
+            4
+        );
+        assert_eq!(
+            KingBucketTable::new(KingBuckets16::TABLE)
+                .unwrap()
+                .bucket_count(),
+            16
+        );
+        assert_eq!(KingBucketTable::new([0; 32]).unwrap().bucket_count(), 1);
+
+        let mut skips_bucket = [0; 32];
+        skips_bucket[31] = 2;
+        assert!(KingBucketTable::new(skips_bucket).is_none());
+    }
+}
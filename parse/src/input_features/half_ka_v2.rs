@@ -0,0 +1,80 @@
+use cozy_chess::{Board, Color, Piece, Square};
+
+use crate::batch::EntryFeatureWriter;
+
+use super::{Factorize, FeatureOptions, InputFeatureSet};
+
+pub struct HalfKaV2;
+
+// Both kings share one plane, as the perspective king's own plane has a single active square
+const PLANES: usize = Color::NUM * (Piece::NUM - 1) + 1;
+
+impl InputFeatureSet for HalfKaV2 {
+    const MAX_FEATURES: usize = 32;
+    const NUM_INPUTS: usize = Square::NUM * PLANES * Square::NUM;
+
+    fn add_features(board: Board, mut entry: EntryFeatureWriter, _options: &FeatureOptions) {
+        let stm = board.side_to_move();
+
+        let stm_king = board.king(stm);
+        let nstm_king = board.king(!stm);
+
+        for &color in &Color::ALL {
+            for &piece in &Piece::ALL {
+                for square in board.pieces(piece) & board.colors(color) {
+                    let stm_feature = feature(stm, stm_king, color, piece, square);
+                    let nstm_feature = feature(!stm, nstm_king, color, piece, square);
+                    entry.add_feature(stm_feature as i64, nstm_feature as i64);
+                }
+            }
+        }
+    }
+}
+
+impl Factorize for HalfKaV2 {
+    const FACTOR_INPUTS: usize = PLANES * Square::NUM;
+}
+
+fn feature(perspective: Color, king: Square, color: Color, piece: Piece, square: Square) -> usize {
+    let (king, square, color) = match perspective {
+        Color::White => (king, square, color),
+        Color::Black => (king.flip_rank(), square.flip_rank(), !color),
+    };
+    let plane = match piece {
+        Piece::King => PLANES - 1,
+        _ => color as usize * (Piece::NUM - 1) + piece as usize,
+    };
+    let mut index = 0;
+    index = index * Square::NUM + king as usize;
+    index = index * PLANES + plane;
+    index = index * Square::NUM + square as usize;
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plane(feature: usize) -> usize {
+        feature / Square::NUM % PLANES
+    }
+
+    #[test]
+    fn kings_share_a_plane() {
+        for &perspective in &Color::ALL {
+            let king = Square::E1.relative_to(perspective);
+            for &color in &Color::ALL {
+                let own_king = feature(perspective, king, color, Piece::King, Square::E1);
+                let enemy_king = feature(perspective, king, color, Piece::King, Square::E8);
+                assert_eq!(plane(own_king), PLANES - 1);
+                assert_eq!(plane(enemy_king), PLANES - 1);
+
+                for &piece in &Piece::ALL[..Piece::NUM - 1] {
+                    let feature = feature(perspective, king, color, piece, Square::D4);
+                    assert!(plane(feature) < PLANES - 1);
+                    assert!(feature < HalfKaV2::NUM_INPUTS);
+                }
+            }
+        }
+    }
+}
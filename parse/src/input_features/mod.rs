@@ -3,54 +3,42 @@ use cozy_chess::Board;
 use crate::batch::EntryFeatureWriter;
 
 mod board_768;
+mod board_768_mirrored;
+mod board_768_threats;
+mod factorized;
 mod half_ka;
+mod half_ka_mirrored;
+mod half_ka_v2;
 mod half_kp;
 
 pub use board_768::Board768;
-pub use board_768::Board768Cuda;
+pub use board_768_mirrored::Board768Mirrored;
+pub use board_768_threats::Board768Threats;
+pub use factorized::Factorize;
+pub use factorized::HalfKaFactorized;
+pub use factorized::HalfKaMirrored16Factorized;
+pub use factorized::HalfKaMirrored4Factorized;
+pub use factorized::HalfKaV2Factorized;
+pub use factorized::HalfKpFactorized;
 pub use half_ka::HalfKa;
-pub use half_ka::HalfKaCuda;
+pub use half_ka_mirrored::HalfKaMirrored16;
+pub use half_ka_mirrored::HalfKaMirrored4;
+pub use half_ka_mirrored::KingBucketTable;
+pub use half_ka_v2::HalfKaV2;
 pub use half_kp::HalfKp;
-pub use half_kp::HalfKpCuda;
 
-pub trait InputFeatureSet {
-    const INDICES_PER_FEATURE: usize;
-    const MAX_FEATURES: usize;
-
-    fn add_features(board: Board, entry: EntryFeatureWriter);
+// Settings of the feature sets configured at runtime
+#[derive(Clone, Debug, Default)]
+pub struct FeatureOptions {
+    // Replaces the king bucket table of feature sets with king buckets
+    pub king_buckets: Option<KingBucketTable>,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-pub enum InputFeatureSetType {
-    Board768,
-    HalfKp,
-    HalfKa,
-    Board768Cuda,
-    HalfKpCuda,
-    HalfKaCuda,
-}
-
-impl InputFeatureSetType {
-    pub fn max_features(self) -> usize {
-        match self {
-            InputFeatureSetType::Board768 => Board768::MAX_FEATURES,
-            InputFeatureSetType::HalfKp => HalfKp::MAX_FEATURES,
-            InputFeatureSetType::HalfKa => HalfKa::MAX_FEATURES,
-            InputFeatureSetType::Board768Cuda => Board768Cuda::MAX_FEATURES,
-            InputFeatureSetType::HalfKpCuda => HalfKpCuda::MAX_FEATURES,
-            InputFeatureSetType::HalfKaCuda => HalfKaCuda::MAX_FEATURES,
-        }
-    }
+pub trait InputFeatureSet {
+    const MAX_FEATURES: usize;
+    const NUM_INPUTS: usize;
+    // The king buckets a runtime king bucket table must use with this feature set
+    const KING_BUCKETS: usize = 0;
 
-    pub fn indices_per_feature(self) -> usize {
-        match self {
-            InputFeatureSetType::Board768 => Board768::INDICES_PER_FEATURE,
-            InputFeatureSetType::HalfKp => HalfKp::INDICES_PER_FEATURE,
-            InputFeatureSetType::HalfKa => HalfKa::INDICES_PER_FEATURE,
-            InputFeatureSetType::Board768Cuda => Board768Cuda::INDICES_PER_FEATURE,
-            InputFeatureSetType::HalfKpCuda => HalfKpCuda::INDICES_PER_FEATURE,
-            InputFeatureSetType::HalfKaCuda => HalfKaCuda::INDICES_PER_FEATURE,
-        }
-    }
+    fn add_features(board: Board, entry: EntryFeatureWriter, options: &FeatureOptions);
 }
@@ -1,36 +1,464 @@
+// The exported functions only take pointers handed out by this library, or null where noted
+#![allow(clippy::missing_safety_doc)]
+
 use std::ffi::CStr;
 use std::os::raw::c_char;
 
 use batch::Batch;
-use bucketing::BucketingSchemeType;
-use input_features::InputFeatureSetType;
+use bucketing::{Bucketing, CustomMaterial};
+use config::ScorePerspective;
+use filters::FilterRule;
+use input_features::KingBucketTable;
+use target::LambdaSchedule;
+
+pub use crate::batch::BatchLayout;
+pub use crate::config::LoaderConfig;
+pub use crate::data_loader::BatchReader;
+pub use crate::registry::{BucketingSchemeType, InputFeatureSetType};
+pub use crate::stats::LoaderStage;
 
-use crate::data_loader::BatchReader;
+// Bumped on every incompatible change to the exported functions and types, including the ids of
+// feature sets and bucketing schemes
+const ABI_VERSION: u32 = 1;
 
+mod augment;
 mod batch;
+mod board_source;
 mod bucketing;
+mod config;
 mod data_loader;
+mod filters;
 mod input_features;
+#[cfg(feature = "python")]
+mod python;
+mod registry;
+mod stats;
+mod tablebase;
+mod target;
+mod weights;
+
+// Maps the value of a C enum to the variant it mirrors, so out of range values are rejected
+fn variant<T: Copy>(variants: &[T], value: u32) -> Option<T> {
+    variants.get(value as usize).copied()
+}
+
+// The getters and setters are written out rather than generated by macros, so that cbindgen sees
+// them without expanding the crate, which needs a nightly toolchain
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_capacity(batch: *mut Batch) -> u32 {
+    batch.as_mut().unwrap().capacity() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_len(batch: *mut Batch) -> u32 {
+    batch.as_mut().unwrap().len() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_stm_feature_buffer_ptr(batch: *mut Batch) -> *const i64 {
+    batch.as_mut().unwrap().stm_feature_buffer_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_nstm_feature_buffer_ptr(batch: *mut Batch) -> *const i64 {
+    batch.as_mut().unwrap().nstm_feature_buffer_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_values_ptr(batch: *mut Batch) -> *const f32 {
+    batch.as_mut().unwrap().values_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_offsets_ptr(batch: *mut Batch) -> *const i64 {
+    batch.as_mut().unwrap().offsets_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_total_features(batch: *mut Batch) -> u32 {
+    batch.as_mut().unwrap().total_features() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_indices_per_feature(batch: *mut Batch) -> u32 {
+    batch.as_mut().unwrap().indices_per_feature() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_cp_ptr(batch: *mut Batch) -> *const f32 {
+    batch.as_mut().unwrap().cp_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_wdl_ptr(batch: *mut Batch) -> *const f32 {
+    batch.as_mut().unwrap().wdl_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_target_ptr(batch: *mut Batch) -> *const f32 {
+    batch.as_mut().unwrap().target_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_weight_ptr(batch: *mut Batch) -> *const f32 {
+    batch.as_mut().unwrap().weight_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_bucket_ptr(batch: *mut Batch) -> *const i32 {
+    batch.as_mut().unwrap().bucket_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_bucket_outputs(batch: *mut Batch) -> u32 {
+    batch.as_mut().unwrap().bucket_outputs() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_stm_mirrored_ptr(batch: *mut Batch) -> *const bool {
+    batch.as_mut().unwrap().stm_mirrored_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_get_nstm_mirrored_ptr(batch: *mut Batch) -> *const bool {
+    batch.as_mut().unwrap().nstm_mirrored_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_mmap(config: *mut LoaderConfig, value: bool) {
+    config.as_mut().unwrap().mmap = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_shuffle_chunks(config: *mut LoaderConfig, value: bool) {
+    config.as_mut().unwrap().shuffle_chunks = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_seed(config: *mut LoaderConfig, value: u64) {
+    config.as_mut().unwrap().seed = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_epoch(config: *mut LoaderConfig, value: u64) {
+    config.as_mut().unwrap().epoch = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_batches_per_chunk(
+    config: *mut LoaderConfig,
+    value: u32,
+) {
+    config.as_mut().unwrap().batches_per_chunk = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_prefetch_depth(config: *mut LoaderConfig, value: u32) {
+    config.as_mut().unwrap().prefetch_depth = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_threads(config: *mut LoaderConfig, value: u32) {
+    config.as_mut().unwrap().threads = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_collect_timings(config: *mut LoaderConfig, value: bool) {
+    config.as_mut().unwrap().collect_timings = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_score_perspective(
+    config: *mut LoaderConfig,
+    value: u32,
+) -> bool {
+    match variant(&ScorePerspective::ALL, value) {
+        Some(value) => {
+            config.as_mut().unwrap().score_perspective = value;
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_syzygy_max_pieces(
+    config: *mut LoaderConfig,
+    value: u32,
+) {
+    config.as_mut().unwrap().syzygy_max_pieces = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_syzygy_rescore_cp(
+    config: *mut LoaderConfig,
+    value: bool,
+) {
+    config.as_mut().unwrap().syzygy_rescore_cp = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_syzygy_win_cp(config: *mut LoaderConfig, value: f32) {
+    config.as_mut().unwrap().syzygy_win_cp = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_augment_mirror_probability(
+    config: *mut LoaderConfig,
+    value: f64,
+) {
+    config.as_mut().unwrap().augment_mirror_probability = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_augment_color_flip_probability(
+    config: *mut LoaderConfig,
+    value: f64,
+) {
+    config.as_mut().unwrap().augment_color_flip_probability = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_augment_seed(config: *mut LoaderConfig, value: u64) {
+    config.as_mut().unwrap().augment_seed = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_keep_probability(config: *mut LoaderConfig, value: f64) {
+    config.as_mut().unwrap().keep_probability = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_min_fullmove(config: *mut LoaderConfig, value: u16) {
+    config.as_mut().unwrap().min_fullmove = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_filter_in_check(config: *mut LoaderConfig, value: bool) {
+    config.as_mut().unwrap().filter_in_check = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_filter_max_eval(config: *mut LoaderConfig, value: f32) {
+    config.as_mut().unwrap().filter_max_eval = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_filter_wdl_margin(
+    config: *mut LoaderConfig,
+    value: f32,
+) {
+    config.as_mut().unwrap().filter_wdl_margin = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_filter_captures(config: *mut LoaderConfig, value: bool) {
+    config.as_mut().unwrap().filter_captures = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_weight_early_fullmove(
+    config: *mut LoaderConfig,
+    value: u16,
+) {
+    config.as_mut().unwrap().weight_early_fullmove = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_weight_early(config: *mut LoaderConfig, value: f32) {
+    config.as_mut().unwrap().weight_early = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_weight_disagreement_margin(
+    config: *mut LoaderConfig,
+    value: f32,
+) {
+    config.as_mut().unwrap().weight_disagreement_margin = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_weight_disagreement(
+    config: *mut LoaderConfig,
+    value: f32,
+) {
+    config.as_mut().unwrap().weight_disagreement = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_weight_balance_buckets(
+    config: *mut LoaderConfig,
+    value: bool,
+) {
+    config.as_mut().unwrap().weight_balance_buckets = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_target_scale(config: *mut LoaderConfig, value: f32) {
+    config.as_mut().unwrap().target_scale = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_target_wdl_lambda(
+    config: *mut LoaderConfig,
+    value: f32,
+) {
+    config.as_mut().unwrap().target_wdl_lambda = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_target_wdl_lambda_end(
+    config: *mut LoaderConfig,
+    value: f32,
+) {
+    config.as_mut().unwrap().target_wdl_lambda_end = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_target_lambda_schedule(
+    config: *mut LoaderConfig,
+    value: u32,
+) -> bool {
+    match variant(&LambdaSchedule::ALL, value) {
+        Some(value) => {
+            config.as_mut().unwrap().target_lambda_schedule = value;
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_target_eval_limit(
+    config: *mut LoaderConfig,
+    value: f32,
+) {
+    config.as_mut().unwrap().target_eval_limit = value;
+}
 
-macro_rules! export_batch_getters {
-    ($($getter:ident $(as $cast_type:ty)?: $exported:ident -> $type:ty,)*) => {$(
-        #[no_mangle]
-        pub unsafe extern "C" fn $exported(batch: *mut Batch) -> $type {
-            batch.as_mut().unwrap().$getter() $(as $cast_type)*
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_syzygy_path(
+    config: *mut LoaderConfig,
+    path: *const c_char,
+) -> bool {
+    let config = config.as_mut().unwrap();
+    match CStr::from_ptr(path).to_str() {
+        Ok(path) => {
+            config.syzygy_path = Some(path.into());
+            true
         }
-    )*}
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn loader_config_new() -> *mut LoaderConfig {
+    Box::into_raw(Box::new(LoaderConfig::default()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_drop(config: *mut LoaderConfig) {
+    let _ = Box::from_raw(config);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_custom_material(
+    config: *mut LoaderConfig,
+    custom_material: *const CustomMaterial,
+) {
+    config.as_mut().unwrap().custom_material = custom_material.as_ref().cloned();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_add_bucketing_scheme(
+    config: *mut LoaderConfig,
+    bucketing_scheme: BucketingSchemeType,
+) -> bool {
+    if !bucketing_scheme.is_valid() {
+        return false;
+    }
+    let config = config.as_mut().unwrap();
+    config
+        .extra_bucketing
+        .push(Bucketing::Scheme(bucketing_scheme));
+    true
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_add_custom_material(
+    config: *mut LoaderConfig,
+    custom_material: *const CustomMaterial,
+) {
+    let custom_material = custom_material.as_ref().unwrap().clone();
+    let config = config.as_mut().unwrap();
+    config
+        .extra_bucketing
+        .push(Bucketing::CustomMaterial(custom_material));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn custom_material_new(
+    piece_values: *const u32,
+    thresholds: *const u32,
+    threshold_count: u32,
+) -> *mut CustomMaterial {
+    let mut values = [0; 5];
+    values.copy_from_slice(std::slice::from_raw_parts(piece_values, 5));
+    let thresholds: &[u32] = match threshold_count {
+        0 => &[],
+        _ => std::slice::from_raw_parts(thresholds, threshold_count as usize),
+    };
+    match CustomMaterial::new(values, thresholds) {
+        Some(custom_material) => Box::into_raw(Box::new(custom_material)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn custom_material_drop(custom_material: *mut CustomMaterial) {
+    let _ = Box::from_raw(custom_material);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn custom_material_get_bucket_count(
+    custom_material: *const CustomMaterial,
+) -> u32 {
+    custom_material.as_ref().unwrap().bucket_count() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn loader_config_set_king_buckets(
+    config: *mut LoaderConfig,
+    king_buckets: *const KingBucketTable,
+) {
+    config.as_mut().unwrap().king_buckets = king_buckets.as_ref().cloned();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn king_bucket_table_new(buckets: *const u32) -> *mut KingBucketTable {
+    let mut table = [0; 32];
+    for (bucket, &value) in table
+        .iter_mut()
+        .zip(std::slice::from_raw_parts(buckets, 32))
+    {
+        *bucket = value as usize;
+    }
+    match KingBucketTable::new(table) {
+        Some(king_buckets) => Box::into_raw(Box::new(king_buckets)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn king_bucket_table_drop(king_buckets: *mut KingBucketTable) {
+    let _ = Box::from_raw(king_buckets);
 }
-export_batch_getters! {
-    capacity as u32                 : batch_get_capacity -> u32,
-    len as u32                      : batch_get_len -> u32,
-    stm_feature_buffer_ptr          : batch_get_stm_feature_buffer_ptr -> *const i64,
-    nstm_feature_buffer_ptr         : batch_get_nstm_feature_buffer_ptr -> *const i64,
-    values_ptr                      : batch_get_values_ptr -> *const f32,
-    total_features as u32           : batch_get_total_features -> u32,
-    indices_per_feature as u32      : batch_get_indices_per_feature -> u32,
-    cp_ptr                          : batch_get_cp_ptr -> *const f32,
-    wdl_ptr                         : batch_get_wdl_ptr -> *const f32,
-    bucket_ptr                      : batch_get_bucket_ptr -> *const i32,
+
+#[no_mangle]
+pub unsafe extern "C" fn king_bucket_table_get_bucket_count(
+    king_buckets: *const KingBucketTable,
+) -> u32 {
+    king_buckets.as_ref().unwrap().bucket_count() as u32
 }
 
 #[no_mangle]
@@ -38,15 +466,24 @@ pub unsafe extern "C" fn batch_reader_new(
     path: *const c_char,
     batch_size: u32,
     feature_set: InputFeatureSetType,
+    layout: u32,
     bucketing_scheme: BucketingSchemeType,
+    config: *const LoaderConfig,
 ) -> *mut BatchReader {
     let reader = (|| {
+        if !feature_set.is_valid() || !bucketing_scheme.is_valid() {
+            return None;
+        }
+        let layout = variant(&BatchLayout::ALL, layout)?;
         let path = CStr::from_ptr(path).to_str().ok()?;
+        let config = config.as_ref().cloned().unwrap_or_default();
         let reader = BatchReader::new(
             path.as_ref(),
             feature_set,
+            layout,
             bucketing_scheme,
             batch_size as usize,
+            &config,
         )
         .ok()?;
         Some(reader)
@@ -63,29 +500,142 @@ pub unsafe extern "C" fn batch_reader_dataset_size(reader: *mut BatchReader) ->
     reader.dataset_size()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn batch_reader_rescored_positions(reader: *mut BatchReader) -> u64 {
+    let reader = reader.as_mut().unwrap();
+    reader.stats().rescored()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_reader_filtered_positions(
+    reader: *mut BatchReader,
+    rule: u32,
+) -> u64 {
+    let reader = reader.as_mut().unwrap();
+    variant(&FilterRule::ALL, rule).map_or(0, |rule| reader.stats().filtered(rule))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_reader_stage_nanos(reader: *mut BatchReader, stage: u32) -> u64 {
+    let reader = reader.as_mut().unwrap();
+    variant(&LoaderStage::ALL, stage).map_or(0, |stage| reader.stats().stage_nanos(stage))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn batch_reader_threads(reader: *mut BatchReader) -> u32 {
+    let reader = reader.as_mut().unwrap();
+    reader.threads() as u32
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn batch_reader_drop(reader: *mut BatchReader) {
     let _ = Box::from_raw(reader);
 }
 
+#[no_mangle]
+pub extern "C" fn parse_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn input_feature_set_count() -> u32 {
+    InputFeatureSetType::count() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn input_feature_set_from_name(name: *const c_char) -> i32 {
+    match InputFeatureSetType::from_name(CStr::from_ptr(name).to_bytes()) {
+        Some(feature_set) => feature_set.0 as i32,
+        None => -1,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn input_feature_set_get_max_features(
     feature_set: InputFeatureSetType,
 ) -> u32 {
+    if !feature_set.is_valid() {
+        return 0;
+    }
     feature_set.max_features() as u32
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn input_feature_set_get_indices_per_feature(
+pub unsafe extern "C" fn input_feature_set_get_num_inputs(feature_set: InputFeatureSetType) -> u32 {
+    if !feature_set.is_valid() {
+        return 0;
+    }
+    feature_set.num_inputs() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn input_feature_set_get_king_buckets(
     feature_set: InputFeatureSetType,
 ) -> u32 {
-    feature_set.indices_per_feature() as u32
+    if !feature_set.is_valid() {
+        return 0;
+    }
+    feature_set.king_buckets() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn input_feature_set_get_name(
+    feature_set: InputFeatureSetType,
+) -> *const c_char {
+    if !feature_set.is_valid() {
+        return std::ptr::null();
+    }
+    feature_set.name().as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn input_feature_set_get_description(
+    feature_set: InputFeatureSetType,
+) -> *const c_char {
+    if !feature_set.is_valid() {
+        return std::ptr::null();
+    }
+    feature_set.description().as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn batch_layout_get_indices_per_feature(layout: u32) -> u32 {
+    match variant(&BatchLayout::ALL, layout) {
+        Some(layout) => layout.indices_per_feature() as u32,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bucketing_scheme_count() -> u32 {
+    BucketingSchemeType::count() as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bucketing_scheme_from_name(name: *const c_char) -> i32 {
+    match BucketingSchemeType::from_name(CStr::from_ptr(name).to_bytes()) {
+        Some(bucketing_scheme) => bucketing_scheme.0 as i32,
+        None => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bucketing_scheme_get_name(
+    bucketing_scheme: BucketingSchemeType,
+) -> *const c_char {
+    if !bucketing_scheme.is_valid() {
+        return std::ptr::null();
+    }
+    bucketing_scheme.name().as_ptr()
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bucketing_scheme_get_bucket_count(
     bucketing_scheme: BucketingSchemeType,
 ) -> u32 {
+    if !bucketing_scheme.is_valid() {
+        return 0;
+    }
     bucketing_scheme.bucket_count() as u32
 }
 
@@ -0,0 +1,341 @@
+use std::path::PathBuf;
+
+use numpy::ndarray::{ArrayView, Dimension, StrideShape};
+use numpy::{Element, PyArray};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::batch::{Batch, BatchLayout};
+use crate::bucketing::{Bucketing, CustomMaterial};
+use crate::config::{LoaderConfig, ScorePerspective};
+use crate::data_loader::BatchReader;
+use crate::filters::FilterRule;
+use crate::input_features::KingBucketTable;
+use crate::registry::{BucketingSchemeType, InputFeatureSetType};
+use crate::stats::LoaderStage;
+use crate::target::LambdaSchedule;
+
+// Iterating yields a dict of arrays named like the fields of `Batch` in trainer/dataloader.py. The
+// arrays are read-only views of the reader's buffers, whose chunk is only handed back to the loader
+// once none of its arrays are alive anymore.
+#[pyclass(name = "BatchReader")]
+struct PyBatchReader {
+    reader: BatchReader,
+    // The chunk batches are currently read from, and the index of the next one
+    chunk: Option<Py<Chunk>>,
+    index: usize,
+    // Read chunks which arrays may still view
+    retired: Vec<Py<Chunk>>,
+    // Chunks held by the loader, which is kept at `prefetch_chunks` whenever arrays allow it
+    loader_chunks: usize,
+    prefetch_chunks: usize,
+}
+
+// Owner of the buffers viewed by the arrays of a chunk's batches
+#[pyclass]
+struct Chunk {
+    batches: Vec<Batch>,
+}
+
+#[pymethods]
+impl PyBatchReader {
+    #[new]
+    fn new(
+        path: &str,
+        batch_size: u32,
+        feature_set: u32,
+        layout: u32,
+        bucketing_scheme: u32,
+        config: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        let feature_set = InputFeatureSetType(feature_set);
+        if !feature_set.is_valid() {
+            return Err(PyValueError::new_err(format!(
+                "invalid input feature set {}",
+                feature_set.0
+            )));
+        }
+        let layout = variant(&BatchLayout::ALL, layout, "batch layout")?;
+        let config = match config {
+            Some(config) => loader_config(config)?,
+            None => LoaderConfig::default(),
+        };
+        let reader = BatchReader::new(
+            path.as_ref(),
+            feature_set,
+            layout,
+            bucketing_scheme_from_id(bucketing_scheme)?,
+            batch_size as usize,
+            &config,
+        )?;
+        let prefetch_chunks = config.prefetch_depth as usize + 1;
+        Ok(Self {
+            reader,
+            chunk: None,
+            index: 0,
+            retired: Vec::new(),
+            loader_chunks: prefetch_chunks,
+            prefetch_chunks,
+        })
+    }
+
+    fn dataset_size(&self) -> u64 {
+        self.reader.dataset_size()
+    }
+
+    fn rescored_positions(&self) -> u64 {
+        self.reader.stats().rescored()
+    }
+
+    fn filtered_positions(&self, rule: u32) -> PyResult<u64> {
+        let rule = variant(&FilterRule::ALL, rule, "filter rule")?;
+        Ok(self.reader.stats().filtered(rule))
+    }
+
+    fn stage_seconds(&self, stage: u32) -> PyResult<f64> {
+        let stage = variant(&LoaderStage::ALL, stage, "loader stage")?;
+        Ok(self.reader.stats().stage_nanos(stage) as f64 / 1e9)
+    }
+
+    fn threads(&self) -> usize {
+        self.reader.threads()
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(slf: &PyCell<Self>) -> PyResult<Option<&PyDict>> {
+        let py = slf.py();
+        let mut this = slf.try_borrow_mut()?;
+        this.recycle_unused(py);
+        loop {
+            if let Some(chunk) = this.chunk.as_ref().map(|chunk| chunk.clone_ref(py)) {
+                let chunk = chunk.into_ref(py);
+                let batches = &chunk.borrow().batches;
+                while this.index < batches.len() {
+                    let batch = &batches[this.index];
+                    this.index += 1;
+                    if batch.len() > 0 {
+                        return batch_arrays(batch, chunk.as_ref()).map(Some);
+                    }
+                }
+            }
+
+            if let Some(chunk) = this.chunk.take() {
+                this.retired.push(chunk);
+                this.recycle_unused(py);
+            }
+            // Every chunk is viewed by arrays, so the loader needs a new one to make progress
+            if this.loader_chunks == 0 {
+                let batches = this.reader.new_chunk();
+                this.reader.recycle_chunk(batches);
+                this.loader_chunks += 1;
+            }
+            let reader = &mut this.reader;
+            // Waiting for the parsing threads shouldn't block other Python threads
+            match py.allow_threads(move || reader.recv_chunk()) {
+                Some(batches) => {
+                    this.loader_chunks -= 1;
+                    this.chunk = Some(Py::new(py, Chunk { batches })?);
+                    this.index = 0;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl PyBatchReader {
+    // Hands retired chunks without arrays back to the loader, or frees them if it has enough
+    fn recycle_unused(&mut self, py: Python) {
+        let mut i = 0;
+        while i < self.retired.len() {
+            if self.retired[i].get_refcnt(py) > 1 {
+                i += 1;
+                continue;
+            }
+            let chunk = self.retired.swap_remove(i);
+            if self.loader_chunks < self.prefetch_chunks {
+                let batches = std::mem::take(&mut chunk.borrow_mut(py).batches);
+                self.reader.recycle_chunk(batches);
+                self.loader_chunks += 1;
+            }
+        }
+    }
+}
+
+fn batch_arrays<'py>(batch: &Batch, container: &'py PyAny) -> PyResult<&'py PyDict> {
+    let len = batch.len();
+    let features = batch.total_features();
+    let indices = features * batch.indices_per_feature();
+    let arrays = PyDict::new(container.py());
+    unsafe {
+        arrays.set_item(
+            "stm_indices",
+            borrow_array(batch.stm_feature_buffer_ptr(), indices, container)?,
+        )?;
+        arrays.set_item(
+            "nstm_indices",
+            borrow_array(batch.nstm_feature_buffer_ptr(), indices, container)?,
+        )?;
+        arrays.set_item(
+            "values",
+            borrow_array(batch.values_ptr(), features, container)?,
+        )?;
+        arrays.set_item(
+            "offsets",
+            borrow_array(batch.offsets_ptr(), len, container)?,
+        )?;
+        arrays.set_item("cp", borrow_array(batch.cp_ptr(), (len, 1), container)?)?;
+        arrays.set_item("wdl", borrow_array(batch.wdl_ptr(), (len, 1), container)?)?;
+        arrays.set_item(
+            "target",
+            borrow_array(batch.target_ptr(), (len, 1), container)?,
+        )?;
+        arrays.set_item(
+            "weights",
+            borrow_array(batch.weight_ptr(), (len, 1), container)?,
+        )?;
+        arrays.set_item(
+            "buckets",
+            borrow_array(batch.bucket_ptr(), (len, batch.bucket_outputs()), container)?,
+        )?;
+        arrays.set_item(
+            "stm_mirrored",
+            borrow_array(batch.stm_mirrored_ptr(), (len, 1), container)?,
+        )?;
+        arrays.set_item(
+            "nstm_mirrored",
+            borrow_array(batch.nstm_mirrored_ptr(), (len, 1), container)?,
+        )?;
+    }
+    arrays.set_item("size", len)?;
+    Ok(arrays)
+}
+
+// Views the memory at `ptr` as a read-only array which keeps `container` alive
+unsafe fn borrow_array<T, D, S>(
+    ptr: *const T,
+    shape: S,
+    container: &PyAny,
+) -> PyResult<&PyArray<T, D>>
+where
+    T: Element,
+    D: Dimension,
+    S: Into<StrideShape<D>>,
+{
+    let array = PyArray::borrow_from_array(&ArrayView::from_shape_ptr(shape, ptr), container);
+    array.call_method1("setflags", (false,))?;
+    Ok(array)
+}
+
+// Maps the value of an IntEnum in trainer/dataloader.py to the variant it mirrors
+fn variant<T: Copy>(variants: &[T], value: u32, kind: &str) -> PyResult<T> {
+    crate::variant(variants, value)
+        .ok_or_else(|| PyValueError::new_err(format!("invalid {} {}", kind, value)))
+}
+
+fn bucketing_scheme_from_id(id: u32) -> PyResult<BucketingSchemeType> {
+    let bucketing_scheme = BucketingSchemeType(id);
+    match bucketing_scheme.is_valid() {
+        true => Ok(bucketing_scheme),
+        false => Err(PyValueError::new_err(format!(
+            "invalid bucketing scheme {}",
+            id
+        ))),
+    }
+}
+
+// Reads a `CustomMaterial` dataclass from trainer/dataloader.py
+fn custom_material(object: &PyAny) -> PyResult<CustomMaterial> {
+    let piece_values: [u32; 5] = object.getattr("piece_values")?.extract()?;
+    let thresholds: Vec<u32> = object.getattr("thresholds")?.extract()?;
+    CustomMaterial::new(piece_values, &thresholds)
+        .ok_or_else(|| PyValueError::new_err("invalid custom material specification"))
+}
+
+// Reads a `KingBucketTable` dataclass from trainer/dataloader.py
+fn king_bucket_table(object: &PyAny) -> PyResult<KingBucketTable> {
+    let table: [usize; 32] = object.getattr("buckets")?.extract()?;
+    KingBucketTable::new(table).ok_or_else(|| PyValueError::new_err("invalid king bucket table"))
+}
+
+macro_rules! extract_config_fields {
+    ($config:ident, $object:ident: $($field:ident,)*) => {$(
+        $config.$field = $object.getattr(stringify!($field))?.extract()?;
+    )*}
+}
+
+// Reads a `LoaderConfig` dataclass from trainer/dataloader.py, whose fields mirror the struct's
+fn loader_config(object: &PyAny) -> PyResult<LoaderConfig> {
+    let mut config = LoaderConfig::default();
+    extract_config_fields! {
+        config, object:
+        mmap,
+        shuffle_chunks,
+        seed,
+        epoch,
+        batches_per_chunk,
+        prefetch_depth,
+        threads,
+        collect_timings,
+        syzygy_max_pieces,
+        syzygy_rescore_cp,
+        syzygy_win_cp,
+        augment_mirror_probability,
+        augment_color_flip_probability,
+        augment_seed,
+        keep_probability,
+        min_fullmove,
+        filter_in_check,
+        filter_max_eval,
+        filter_wdl_margin,
+        filter_captures,
+        weight_early_fullmove,
+        weight_early,
+        weight_disagreement_margin,
+        weight_disagreement,
+        weight_balance_buckets,
+        target_scale,
+        target_wdl_lambda,
+        target_wdl_lambda_end,
+        target_eval_limit,
+    }
+    config.score_perspective = variant(
+        &ScorePerspective::ALL,
+        object.getattr("score_perspective")?.extract()?,
+        "score perspective",
+    )?;
+    config.target_lambda_schedule = variant(
+        &LambdaSchedule::ALL,
+        object.getattr("target_lambda_schedule")?.extract()?,
+        "lambda schedule",
+    )?;
+
+    let custom: Option<&PyAny> = object.getattr("custom_material")?.extract()?;
+    config.custom_material = custom.map(custom_material).transpose()?;
+    for bucketing in object.getattr("extra_bucketing")?.iter()? {
+        let bucketing = bucketing?;
+        config
+            .extra_bucketing
+            .push(match bucketing.hasattr("piece_values")? {
+                true => Bucketing::CustomMaterial(custom_material(bucketing)?),
+                false => Bucketing::Scheme(bucketing_scheme_from_id(bucketing.extract()?)?),
+            });
+    }
+    let king_buckets: Option<&PyAny> = object.getattr("king_buckets")?.extract()?;
+    config.king_buckets = king_buckets.map(king_bucket_table).transpose()?;
+    let syzygy_path: Option<String> = object.getattr("syzygy_path")?.extract()?;
+    config.syzygy_path = syzygy_path.map(PathBuf::from);
+    Ok(config)
+}
+
+#[pymodule]
+fn parse(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("ABI_VERSION", crate::ABI_VERSION)?;
+    m.add_class::<PyBatchReader>()?;
+    m.add_class::<Chunk>()?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use std::ffi::CStr;
+
+use cozy_chess::Board;
+use marlinformat::PackedBoard;
+
+use crate::batch::Batch;
+use crate::bucketing::*;
+use crate::data_loader::{process, LoaderContext};
+use crate::input_features::*;
+
+macro_rules! c_str {
+    ($($s:tt)*) => {
+        unsafe { CStr::from_bytes_with_nul_unchecked(concat!($($s)*, "\0").as_bytes()) }
+    };
+}
+
+macro_rules! registry {
+    (
+        feature_sets: {$($feature_set:ident: $description:literal,)*}
+        bucketing_schemes: [$($bucketing_scheme:ident,)*]
+    ) => {
+        static FEATURE_SETS: &[FeatureSetInfo] = &[$(
+            FeatureSetInfo {
+                name: c_str!(stringify!($feature_set)),
+                description: c_str!($description),
+                max_features: $feature_set::MAX_FEATURES,
+                num_inputs: $feature_set::NUM_INPUTS,
+                king_buckets: $feature_set::KING_BUCKETS,
+                process: process::<$feature_set>,
+            },
+        )*];
+
+        static BUCKETING_SCHEMES: &[BucketingSchemeInfo] = &[$(
+            BucketingSchemeInfo {
+                name: c_str!(stringify!($bucketing_scheme)),
+                bucket_count: $bucketing_scheme::BUCKET_COUNT,
+                bucket: $bucketing_scheme::bucket,
+            },
+        )*];
+    };
+}
+
+// Feature sets and bucketing schemes are identified over FFI by their index in these lists, so new
+// ones must be appended.
+registry! {
+    feature_sets: {
+        Board768: "Piece-square features relative to the side to move",
+        HalfKp: "King-square x non-king piece-square features",
+        HalfKa: "King-square x piece-square features",
+        HalfKaMirrored4: "HalfKA with 4 king buckets, mirrored so the king is on files a-d",
+        HalfKaMirrored16: "HalfKA with 16 king buckets, mirrored so the king is on files a-d",
+        Board768Mirrored: "Board768 mirrored so the perspective king is on files a-d",
+        HalfKaV2: "HalfKA with both king planes merged into one",
+        HalfKpFactorized: "HalfKP with king-independent virtual piece-square features",
+        HalfKaFactorized: "HalfKA with king-independent virtual piece-square features",
+        HalfKaMirrored4Factorized: "HalfKaMirrored4 with king-independent virtual piece-square features",
+        HalfKaMirrored16Factorized: "HalfKaMirrored16 with king-independent virtual piece-square features",
+        HalfKaV2Factorized: "HalfKaV2 with king-independent virtual piece-square features",
+        Board768Threats: "Board768 with features for pieces attacked or defended by each piece type",
+    }
+    bucketing_schemes: [
+        NoBucketing,
+        ModifiedMaterial,
+        PieceCount,
+        KingZone,
+        CastledKings,
+        PawnCount,
+        PassedPawns,
+    ]
+}
+
+pub type ProcessFn = fn(&mut Batch, &[PackedBoard], &LoaderContext, u64);
+
+pub struct FeatureSetInfo {
+    name: &'static CStr,
+    description: &'static CStr,
+    max_features: usize,
+    num_inputs: usize,
+    king_buckets: usize,
+    process: ProcessFn,
+}
+
+pub struct BucketingSchemeInfo {
+    name: &'static CStr,
+    bucket_count: usize,
+    bucket: fn(&Board) -> i32,
+}
+
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InputFeatureSetType(pub u32);
+
+impl InputFeatureSetType {
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..FEATURE_SETS.len() as u32).map(InputFeatureSetType)
+    }
+
+    pub fn count() -> usize {
+        FEATURE_SETS.len()
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::all().find(|feature_set| feature_set.name().to_bytes() == name)
+    }
+
+    pub fn is_valid(self) -> bool {
+        (self.0 as usize) < FEATURE_SETS.len()
+    }
+
+    fn info(self) -> &'static FeatureSetInfo {
+        &FEATURE_SETS[self.0 as usize]
+    }
+
+    pub fn name(self) -> &'static CStr {
+        self.info().name
+    }
+
+    pub fn description(self) -> &'static CStr {
+        self.info().description
+    }
+
+    pub fn max_features(self) -> usize {
+        self.info().max_features
+    }
+
+    pub fn num_inputs(self) -> usize {
+        self.info().num_inputs
+    }
+
+    pub fn king_buckets(self) -> usize {
+        self.info().king_buckets
+    }
+
+    pub fn process_fn(self) -> ProcessFn {
+        self.info().process
+    }
+}
+
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BucketingSchemeType(pub u32);
+
+impl BucketingSchemeType {
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..BUCKETING_SCHEMES.len() as u32).map(BucketingSchemeType)
+    }
+
+    pub fn count() -> usize {
+        BUCKETING_SCHEMES.len()
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::all().find(|bucketing_scheme| bucketing_scheme.name().to_bytes() == name)
+    }
+
+    pub fn is_valid(self) -> bool {
+        (self.0 as usize) < BUCKETING_SCHEMES.len()
+    }
+
+    fn info(self) -> &'static BucketingSchemeInfo {
+        &BUCKETING_SCHEMES[self.0 as usize]
+    }
+
+    pub fn name(self) -> &'static CStr {
+        self.info().name
+    }
+
+    pub fn bucket_count(self) -> usize {
+        self.info().bucket_count
+    }
+
+    pub fn bucket(self, board: &Board) -> i32 {
+        (self.info().bucket)(board)
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::filters::FilterRule;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub enum LoaderStage {
+    // Reading chunks from the dataset
+    Io,
+    // Unpacking positions, only measured with `collect_timings`
+    Unpack,
+    // Writing entries and features, only measured with `collect_timings`
+    Featurize,
+    // Time spent in `process`, summed over worker threads
+    Busy,
+    // Wall time of parsing chunks
+    Parse,
+}
+
+impl LoaderStage {
+    pub const NUM: usize = 5;
+    pub const ALL: [LoaderStage; LoaderStage::NUM] = [
+        LoaderStage::Io,
+        LoaderStage::Unpack,
+        LoaderStage::Featurize,
+        LoaderStage::Busy,
+        LoaderStage::Parse,
+    ];
+}
+
+#[derive(Default)]
+pub struct LoaderStats {
+    rescored: AtomicU64,
+    filtered: [AtomicU64; FilterRule::NUM],
+    stage_nanos: [AtomicU64; LoaderStage::NUM],
+}
+
+impl LoaderStats {
+    pub fn add_rescored(&self, count: u64) {
+        self.rescored.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn rescored(&self) -> u64 {
+        self.rescored.load(Ordering::Relaxed)
+    }
+
+    pub fn add_filtered(&self, counts: &[u64; FilterRule::NUM]) {
+        for (total, &count) in self.filtered.iter().zip(counts) {
+            total.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    pub fn filtered(&self, rule: FilterRule) -> u64 {
+        self.filtered[rule as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn add_stage_nanos(&self, stage: LoaderStage, nanos: u64) {
+        self.stage_nanos[stage as usize].fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    pub fn stage_nanos(&self, stage: LoaderStage) -> u64 {
+        self.stage_nanos[stage as usize].load(Ordering::Relaxed)
+    }
+}
+
+pub fn elapsed_nanos(start: Option<Instant>) -> u64 {
+    start.map_or(0, |start| start.elapsed().as_nanos() as u64)
+}
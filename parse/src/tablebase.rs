@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use cozy_chess::Board;
+use cozy_syzygy::{Tablebase, Wdl};
+
+pub struct Rescorer {
+    tablebase: Tablebase,
+    max_pieces: u32,
+    rescore_cp: bool,
+    win_cp: f32,
+}
+
+impl Rescorer {
+    pub fn new(
+        path: &Path,
+        max_pieces: u32,
+        rescore_cp: bool,
+        win_cp: f32,
+    ) -> std::io::Result<Self> {
+        let mut tablebase = Tablebase::new();
+        tablebase.add_directory(path)?;
+        let max_pieces = match max_pieces {
+            0 => tablebase.max_pieces(),
+            n => n.min(tablebase.max_pieces()),
+        };
+        Ok(Self {
+            tablebase,
+            max_pieces,
+            rescore_cp,
+            win_cp,
+        })
+    }
+
+    // Replaces the targets with the tablebase result, returning whether the position was found in
+    // the tablebase.
+    pub fn rescore(&self, board: &Board, cp: &mut f32, wdl: &mut f32) -> bool {
+        if board.occupied().len() > self.max_pieces {
+            return false;
+        }
+        let board = match syzygy_board(board) {
+            Some(board) => board,
+            None => return false,
+        };
+        match self.tablebase.probe_wdl(&board) {
+            Some(result) => {
+                self.apply(result, cp, wdl);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Tablebase results are relative to the side to move, like the targets
+    fn apply(&self, result: Wdl, cp: &mut f32, wdl: &mut f32) {
+        let (new_wdl, new_cp) = match result {
+            Wdl::Win => (1.0, self.win_cp),
+            Wdl::Loss => (0.0, -self.win_cp),
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => (0.5, 0.0),
+        };
+        *wdl = new_wdl;
+        if self.rescore_cp {
+            *cp = new_cp;
+        }
+    }
+}
+
+// cozy-syzygy takes boards from an older cozy-chess, so positions are passed over as (Shredder) FEN
+fn syzygy_board(board: &Board) -> Option<syzygy_chess::Board> {
+    syzygy_chess::Board::from_fen(&format!("{:#}", board), true).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_syzygy_board() {
+        for fen in &[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 25",
+            "8/8/8/4k3/8/8/8/KQ6 b - - 17 80",
+        ] {
+            let board: Board = fen.parse().unwrap();
+            let converted = syzygy_board(&board).unwrap();
+            assert_eq!(format!("{:#}", converted), format!("{:#}", board));
+        }
+    }
+
+    #[test]
+    fn applies_results() {
+        let rescorer = |rescore_cp| Rescorer {
+            tablebase: Tablebase::new(),
+            max_pieces: 5,
+            rescore_cp,
+            win_cp: 2000.0,
+        };
+        let apply = |rescorer: &Rescorer, result| {
+            let (mut cp, mut wdl) = (150.0, 0.5);
+            rescorer.apply(result, &mut cp, &mut wdl);
+            (cp, wdl)
+        };
+
+        let rescore_cp = rescorer(true);
+        assert_eq!(apply(&rescore_cp, Wdl::Win), (2000.0, 1.0));
+        assert_eq!(apply(&rescore_cp, Wdl::Loss), (-2000.0, 0.0));
+        for result in [Wdl::CursedWin, Wdl::Draw, Wdl::BlessedLoss] {
+            assert_eq!(apply(&rescore_cp, result), (0.0, 0.5));
+        }
+
+        let keep_cp = rescorer(false);
+        assert_eq!(apply(&keep_cp, Wdl::Win), (150.0, 1.0));
+        assert_eq!(apply(&keep_cp, Wdl::Loss), (150.0, 0.0));
+        assert_eq!(apply(&keep_cp, Wdl::CursedWin), (150.0, 0.5));
+    }
+
+    #[test]
+    #[ignore]
+    fn rescores_from_side_to_move() {
+        // Generate the 3 piece syzygy tables into `syzygy/` to run test
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("syzygy");
+        let rescorer = Rescorer::new(&path, 0, true, 2000.0).unwrap();
+
+        let board: Board = "8/8/8/4k3/8/8/8/KQ6 w - - 0 1".parse().unwrap();
+        let (mut cp, mut wdl) = (0.0, 0.5);
+        assert!(rescorer.rescore(&board, &mut cp, &mut wdl));
+        assert_eq!((cp, wdl), (2000.0, 1.0));
+
+        let board: Board = "8/8/8/4k3/8/8/8/KQ6 b - - 0 1".parse().unwrap();
+        let (mut cp, mut wdl) = (0.0, 0.5);
+        assert!(rescorer.rescore(&board, &mut cp, &mut wdl));
+        assert_eq!((cp, wdl), (-2000.0, 0.0));
+
+        let board: Board = "8/8/8/4k3/8/8/8/KN6 w - - 0 1".parse().unwrap();
+        let (mut cp, mut wdl) = (300.0, 1.0);
+        assert!(rescorer.rescore(&board, &mut cp, &mut wdl));
+        assert_eq!((cp, wdl), (0.0, 0.5));
+
+        let board = Board::default();
+        let (mut cp, mut wdl) = (10.0, 1.0);
+        assert!(!rescorer.rescore(&board, &mut cp, &mut wdl));
+        assert_eq!((cp, wdl), (10.0, 1.0));
+    }
+}
@@ -0,0 +1,118 @@
+use cozy_chess::{Board, Piece};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub enum LambdaSchedule {
+    // Always use `wdl_lambda`
+    Constant,
+    // Interpolate from `wdl_lambda` with all pieces on the board to `wdl_lambda_end` with none
+    Phase,
+    // Interpolate from `wdl_lambda` at an eval of 0 to `wdl_lambda_end` at `eval_limit`
+    Eval,
+}
+
+impl LambdaSchedule {
+    pub const ALL: [LambdaSchedule; 3] = [
+        LambdaSchedule::Constant,
+        LambdaSchedule::Phase,
+        LambdaSchedule::Eval,
+    ];
+}
+
+// Blends the eval and game result into `sigmoid(cp / scale) * (1 - lambda) + wdl * lambda`
+pub struct Target {
+    pub scale: f32,
+    pub wdl_lambda: f32,
+    pub wdl_lambda_end: f32,
+    pub schedule: LambdaSchedule,
+    pub eval_limit: f32,
+}
+
+const MAX_PHASE: u32 = 24;
+
+impl Target {
+    pub fn target(&self, board: &Board, cp: f32, wdl: f32) -> f32 {
+        let progress = match self.schedule {
+            LambdaSchedule::Constant => 0.0,
+            LambdaSchedule::Phase => 1.0 - phase(board).min(MAX_PHASE) as f32 / MAX_PHASE as f32,
+            LambdaSchedule::Eval => (cp.abs() / self.eval_limit).min(1.0),
+        };
+        let lambda = self.wdl_lambda + (self.wdl_lambda_end - self.wdl_lambda) * progress;
+        let eval = 1.0 / (1.0 + (-cp / self.scale).exp());
+        eval * (1.0 - lambda) + wdl * lambda
+    }
+}
+
+fn phase(board: &Board) -> u32 {
+    board.pieces(Piece::Knight).len()
+        + board.pieces(Piece::Bishop).len()
+        + 2 * board.pieces(Piece::Rook).len()
+        + 4 * board.pieces(Piece::Queen).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The target the trainer computed from a batch before the loader blended it
+    fn trainer_target(cp: f32, wdl: f32, scale: f32, lambda: f32) -> f32 {
+        let sigmoid = 1.0 / (1.0 + (-cp / scale).exp());
+        sigmoid * (1.0 - lambda) + wdl * lambda
+    }
+
+    fn target(schedule: LambdaSchedule) -> Target {
+        Target {
+            scale: 400.0,
+            wdl_lambda: 0.2,
+            wdl_lambda_end: 0.8,
+            schedule,
+            eval_limit: 1000.0,
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn constant_schedule() {
+        let board = Board::default();
+        let target = target(LambdaSchedule::Constant);
+        for &(cp, wdl) in &[(0.0, 0.5), (150.0, 1.0), (-600.0, 0.0), (3000.0, 0.5)] {
+            let expected = trainer_target(cp, wdl, 400.0, 0.2);
+            assert_close(target.target(&board, cp, wdl), expected);
+        }
+    }
+
+    #[test]
+    fn phase_schedule() {
+        let target = target(LambdaSchedule::Phase);
+        let start = Board::default();
+        let rooks: Board = "r3k3/8/8/8/8/8/8/4K2R w - - 0 1".parse().unwrap();
+        let kings: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        // The two rooks leave a phase of 4 out of 24
+        let rooks_lambda = 0.2 + 0.6 * 20.0 / 24.0;
+        assert_close(
+            target.target(&start, 100.0, 1.0),
+            trainer_target(100.0, 1.0, 400.0, 0.2),
+        );
+        assert_close(
+            target.target(&rooks, 100.0, 1.0),
+            trainer_target(100.0, 1.0, 400.0, rooks_lambda),
+        );
+        assert_close(
+            target.target(&kings, 100.0, 1.0),
+            trainer_target(100.0, 1.0, 400.0, 0.8),
+        );
+    }
+
+    #[test]
+    fn eval_schedule() {
+        let board = Board::default();
+        let target = target(LambdaSchedule::Eval);
+        for &(cp, lambda) in &[(0.0, 0.2), (500.0, 0.5), (-500.0, 0.5), (2000.0, 0.8)] {
+            let expected = trainer_target(cp, 0.0, 400.0, lambda);
+            assert_close(target.target(&board, cp, 0.0), expected);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use cozy_chess::Board;
+
+use crate::filters::disagrees;
+
+pub struct Weights {
+    pub early_fullmove: u16,
+    pub early_weight: f32,
+    pub disagreement_margin: f32,
+    pub disagreement_weight: f32,
+    pub balance_buckets: bool,
+}
+
+impl Weights {
+    pub fn weight(&self, board: &Board, cp: f32, wdl: f32) -> f32 {
+        let mut weight = 1.0;
+        if board.fullmove_number() < self.early_fullmove {
+            weight *= self.early_weight;
+        }
+        if disagrees(cp, wdl, self.disagreement_margin) {
+            weight *= self.disagreement_weight;
+        }
+        weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{Batch, BatchLayout};
+
+    fn weights() -> Weights {
+        Weights {
+            early_fullmove: 10,
+            early_weight: 0.5,
+            disagreement_margin: 200.0,
+            disagreement_weight: 0.25,
+            balance_buckets: false,
+        }
+    }
+
+    #[test]
+    fn early_positions() {
+        let early: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 9".parse().unwrap();
+        let late: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 10".parse().unwrap();
+        assert_eq!(weights().weight(&early, 0.0, 0.5), 0.5);
+        assert_eq!(weights().weight(&late, 0.0, 0.5), 1.0);
+    }
+
+    #[test]
+    fn disagreements() {
+        let board: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 40".parse().unwrap();
+        assert_eq!(weights().weight(&board, -300.0, 1.0), 0.25);
+        assert_eq!(weights().weight(&board, 300.0, 0.0), 0.25);
+        assert_eq!(weights().weight(&board, -200.0, 1.0), 1.0);
+        assert_eq!(weights().weight(&board, 300.0, 1.0), 1.0);
+
+        let early: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(weights().weight(&early, -300.0, 1.0), 0.125);
+    }
+
+    #[test]
+    fn balanced_buckets() {
+        // The primary buckets are balanced, the second output is ignored
+        let primary = [0, 0, 0, 0, 2, 2, 5];
+        let mut batch = Batch::new(16, 1, BatchLayout::Sparse, 2);
+        for (i, &bucket) in primary.iter().enumerate() {
+            let buckets = [bucket, i as i32 % 2];
+            batch.make_entry(0.0, 0.5, 0.5, 1.0, buckets.iter().copied());
+        }
+        batch.balance_bucket_weights();
+
+        let weights = unsafe { std::slice::from_raw_parts(batch.weight_ptr(), batch.len()) };
+        let total = |bucket| -> f32 {
+            primary
+                .iter()
+                .zip(weights)
+                .filter(|&(&primary, _)| primary == bucket)
+                .map(|(_, weight)| weight)
+                .sum()
+        };
+        for &bucket in &[0, 2, 5] {
+            assert!((total(bucket) - primary.len() as f32 / 3.0).abs() < 1e-5);
+        }
+        assert!((weights.iter().sum::<f32>() - batch.len() as f32).abs() < 1e-5);
+    }
+}
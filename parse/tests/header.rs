@@ -0,0 +1,26 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// include/parse.h is committed for C users, run with UPDATE_HEADER=1 to regenerate it
+#[test]
+fn header_is_up_to_date() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::generate_with_config(crate_dir, config).unwrap();
+    let mut header = Vec::new();
+    bindings.write(&mut header);
+
+    let path = crate_dir.join("include/parse.h");
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &header).unwrap();
+        return;
+    }
+    let committed = fs::read(&path).unwrap_or_default();
+    assert!(
+        committed == header,
+        "include/parse.h is out of date, regenerate it with \
+         `UPDATE_HEADER=1 cargo test -p parse --test header`"
+    );
+}
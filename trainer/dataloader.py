@@ -1,6 +1,6 @@
 from __future__ import annotations
 
-from dataclasses import dataclass
+from dataclasses import dataclass, field, replace
 from enum import IntEnum
 from typing import Callable
 
@@ -11,27 +11,132 @@ import numpy as np
 import torch
 
 
+# Must match ABI_VERSION in parse/src/lib.rs
+PARSE_ABI_VERSION = 1
+
+
+def _parse_lib_path() -> str:
+    # The extension module built with the `python` feature exports the C functions as well
+    try:
+        import parse
+
+        if hasattr(parse, "BatchReader"):
+            return parse.__file__
+    except ImportError:
+        pass
+    return "./libparse.dll" if os.name == "nt" else "./libparse.so"
+
+
 def _load_parse_lib():
-    path = "./libparse.dll" if os.name == "nt" else "./libparse.so"
+    path = _parse_lib_path()
     lib = ctypes.cdll.LoadLibrary(path)
 
+    lib.parse_abi_version.restype = ctypes.c_uint32
+    abi_version = lib.parse_abi_version()
+    if abi_version != PARSE_ABI_VERSION:
+        raise ImportError(
+            f"{path} has ABI version {abi_version}, expected {PARSE_ABI_VERSION}"
+        )
+
     lib.batch_get_capacity.restype = ctypes.c_uint32
     lib.batch_get_len.restype = ctypes.c_uint32
     lib.batch_get_stm_feature_buffer_ptr.restype = ctypes.POINTER(ctypes.c_int64)
     lib.batch_get_nstm_feature_buffer_ptr.restype = ctypes.POINTER(ctypes.c_int64)
     lib.batch_get_values_ptr.restype = ctypes.POINTER(ctypes.c_float)
+    lib.batch_get_offsets_ptr.restype = ctypes.POINTER(ctypes.c_int64)
     lib.batch_get_total_features.restype = ctypes.c_uint32
     lib.batch_get_cp_ptr.restype = ctypes.POINTER(ctypes.c_float)
     lib.batch_get_wdl_ptr.restype = ctypes.POINTER(ctypes.c_float)
+    lib.batch_get_target_ptr.restype = ctypes.POINTER(ctypes.c_float)
+    lib.batch_get_weight_ptr.restype = ctypes.POINTER(ctypes.c_float)
     lib.batch_get_bucket_ptr.restype = ctypes.POINTER(ctypes.c_int32)
+    lib.batch_get_bucket_outputs.restype = ctypes.c_uint32
+    lib.batch_get_stm_mirrored_ptr.restype = ctypes.POINTER(ctypes.c_bool)
+    lib.batch_get_nstm_mirrored_ptr.restype = ctypes.POINTER(ctypes.c_bool)
+
+    lib.loader_config_new.restype = ctypes.c_void_p
+    lib.loader_config_drop.restype = None
+    lib.loader_config_set_mmap.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_shuffle_chunks.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_seed.argtypes = [ctypes.c_void_p, ctypes.c_uint64]
+    lib.loader_config_set_epoch.argtypes = [ctypes.c_void_p, ctypes.c_uint64]
+    lib.loader_config_set_batches_per_chunk.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_set_prefetch_depth.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_set_threads.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_set_collect_timings.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_score_perspective.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_set_score_perspective.restype = ctypes.c_bool
+    lib.loader_config_set_syzygy_path.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+    lib.loader_config_set_syzygy_path.restype = ctypes.c_bool
+    lib.loader_config_set_syzygy_max_pieces.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_set_syzygy_rescore_cp.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_syzygy_win_cp.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_augment_mirror_probability.argtypes = [ctypes.c_void_p, ctypes.c_double]
+    lib.loader_config_set_augment_color_flip_probability.argtypes = [
+        ctypes.c_void_p, ctypes.c_double
+    ]
+    lib.loader_config_set_augment_seed.argtypes = [ctypes.c_void_p, ctypes.c_uint64]
+    lib.loader_config_set_keep_probability.argtypes = [ctypes.c_void_p, ctypes.c_double]
+    lib.loader_config_set_min_fullmove.argtypes = [ctypes.c_void_p, ctypes.c_uint16]
+    lib.loader_config_set_filter_in_check.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_filter_max_eval.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_filter_wdl_margin.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_filter_captures.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_weight_early_fullmove.argtypes = [ctypes.c_void_p, ctypes.c_uint16]
+    lib.loader_config_set_weight_early.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_weight_disagreement_margin.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_weight_disagreement.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_weight_balance_buckets.argtypes = [ctypes.c_void_p, ctypes.c_bool]
+    lib.loader_config_set_target_scale.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_target_wdl_lambda.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_target_wdl_lambda_end.argtypes = [ctypes.c_void_p, ctypes.c_float]
+    lib.loader_config_set_target_lambda_schedule.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_set_target_lambda_schedule.restype = ctypes.c_bool
+    lib.loader_config_set_target_eval_limit.argtypes = [ctypes.c_void_p, ctypes.c_float]
+
+    lib.loader_config_set_custom_material.argtypes = [ctypes.c_void_p, ctypes.c_void_p]
+    lib.loader_config_add_bucketing_scheme.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.loader_config_add_bucketing_scheme.restype = ctypes.c_bool
+    lib.loader_config_add_custom_material.argtypes = [ctypes.c_void_p, ctypes.c_void_p]
+
+    lib.custom_material_new.restype = ctypes.c_void_p
+    lib.custom_material_drop.argtypes = [ctypes.c_void_p]
+    lib.custom_material_drop.restype = None
+    lib.custom_material_get_bucket_count.argtypes = [ctypes.c_void_p]
+    lib.custom_material_get_bucket_count.restype = ctypes.c_uint32
+
+    lib.loader_config_set_king_buckets.argtypes = [ctypes.c_void_p, ctypes.c_void_p]
+    lib.king_bucket_table_new.restype = ctypes.c_void_p
+    lib.king_bucket_table_drop.argtypes = [ctypes.c_void_p]
+    lib.king_bucket_table_drop.restype = None
+    lib.king_bucket_table_get_bucket_count.argtypes = [ctypes.c_void_p]
+    lib.king_bucket_table_get_bucket_count.restype = ctypes.c_uint32
 
     lib.batch_reader_new.restype = ctypes.c_void_p
     lib.batch_reader_dataset_size.restype = ctypes.c_uint64
+    lib.batch_reader_rescored_positions.restype = ctypes.c_uint64
+    lib.batch_reader_filtered_positions.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.batch_reader_filtered_positions.restype = ctypes.c_uint64
+    lib.batch_reader_stage_nanos.argtypes = [ctypes.c_void_p, ctypes.c_uint32]
+    lib.batch_reader_stage_nanos.restype = ctypes.c_uint64
+    lib.batch_reader_threads.restype = ctypes.c_uint32
     lib.batch_reader_drop.restype = None
 
+    lib.input_feature_set_count.restype = ctypes.c_uint32
+    lib.input_feature_set_from_name.argtypes = [ctypes.c_char_p]
+    lib.input_feature_set_from_name.restype = ctypes.c_int32
     lib.input_feature_set_get_max_features.restype = ctypes.c_uint32
-    lib.input_feature_set_get_indices_per_feature.restype = ctypes.c_uint32
+    lib.input_feature_set_get_num_inputs.restype = ctypes.c_uint32
+    lib.input_feature_set_get_king_buckets.restype = ctypes.c_uint32
+    lib.input_feature_set_get_name.restype = ctypes.c_char_p
+    lib.input_feature_set_get_description.restype = ctypes.c_char_p
 
+    lib.batch_layout_get_indices_per_feature.restype = ctypes.c_uint32
+
+    lib.bucketing_scheme_count.restype = ctypes.c_uint32
+    lib.bucketing_scheme_from_name.argtypes = [ctypes.c_char_p]
+    lib.bucketing_scheme_from_name.restype = ctypes.c_int32
+    lib.bucketing_scheme_get_name.restype = ctypes.c_char_p
     lib.bucketing_scheme_get_bucket_count.restype = ctypes.c_uint32
 
     lib.read_batch.restype = ctypes.c_void_p
@@ -42,37 +147,293 @@ def _load_parse_lib():
 PARSE_LIB = _load_parse_lib()
 
 
+def _check_ids(enum: type[IntEnum], count: int, get_name: Callable[[int], bytes]) -> None:
+    # `HALF_KA_MIRRORED_4` is `HalfKaMirrored4` in the library
+    library_names = [get_name(i).decode("utf-8").lower() for i in range(count)]
+    names = [member.name.replace("_", "").lower() for member in enum]
+    if names != library_names:
+        raise ImportError(
+            f"{enum.__name__} does not match the library, which provides {library_names}"
+        )
+
+
 class InputFeatureSet(IntEnum):
     BOARD_768 = 0
     HALF_KP = 1
     HALF_KA = 2
-    BOARD_768_CUDA = 3
-    HALF_KP_CUDA = 4
-    HALF_KA_CUDA = 5
+    HALF_KA_MIRRORED_4 = 3
+    HALF_KA_MIRRORED_16 = 4
+    BOARD_768_MIRRORED = 5
+    HALF_KA_V2 = 6
+    HALF_KP_FACTORIZED = 7
+    HALF_KA_FACTORIZED = 8
+    HALF_KA_MIRRORED_4_FACTORIZED = 9
+    HALF_KA_MIRRORED_16_FACTORIZED = 10
+    HALF_KA_V2_FACTORIZED = 11
+    BOARD_768_THREATS = 12
+
+    @classmethod
+    def from_name(cls, name: str) -> InputFeatureSet:
+        value = PARSE_LIB.input_feature_set_from_name(name.encode("utf-8"))
+        if value < 0:
+            raise ValueError(f"unknown input feature set {name}")
+        return cls(value)
 
     def max_features(self) -> int:
         return PARSE_LIB.input_feature_set_get_max_features(self)
 
+    def num_inputs(self) -> int:
+        return PARSE_LIB.input_feature_set_get_num_inputs(self)
+
+    def king_buckets(self) -> int:
+        return PARSE_LIB.input_feature_set_get_king_buckets(self)
+
+    def display_name(self) -> str:
+        return PARSE_LIB.input_feature_set_get_name(self).decode("utf-8")
+
+    def description(self) -> str:
+        return PARSE_LIB.input_feature_set_get_description(self).decode("utf-8")
+
+
+_check_ids(
+    InputFeatureSet, PARSE_LIB.input_feature_set_count(), PARSE_LIB.input_feature_set_get_name
+)
+
+
+class BatchLayout(IntEnum):
+    SPARSE = 0
+    CUDA = 1
+    CSR = 2
+
     def indices_per_feature(self) -> int:
-        return PARSE_LIB.input_feature_set_get_indices_per_feature(self)
+        return PARSE_LIB.batch_layout_get_indices_per_feature(self)
+
 
 class BucketingScheme(IntEnum):
     NO_BUCKETING = 0
     MODIFIED_MATERIAL = 1
     PIECE_COUNT = 2
+    KING_ZONE = 3
+    CASTLED_KINGS = 4
+    PAWN_COUNT = 5
+    PASSED_PAWNS = 6
+
+    @classmethod
+    def from_name(cls, name: str) -> BucketingScheme:
+        value = PARSE_LIB.bucketing_scheme_from_name(name.encode("utf-8"))
+        if value < 0:
+            raise ValueError(f"unknown bucketing scheme {name}")
+        return cls(value)
+
+    def display_name(self) -> str:
+        return PARSE_LIB.bucketing_scheme_get_name(self).decode("utf-8")
 
     def bucket_count(self) -> int:
         return PARSE_LIB.bucketing_scheme_get_bucket_count(self)
 
 
+_check_ids(
+    BucketingScheme, PARSE_LIB.bucketing_scheme_count(), PARSE_LIB.bucketing_scheme_get_name
+)
+
+
+@dataclass(frozen=True)
+class CustomMaterial:
+    # Pawn, knight, bishop, rook, queen
+    piece_values: tuple[int, int, int, int, int] = (1, 3, 3, 5, 8)
+    # Material reaching a threshold moves the position into the next bucket
+    thresholds: tuple[int, ...] = ()
+
+    def __post_init__(self) -> None:
+        PARSE_LIB.custom_material_drop(self.to_parser_custom_material())
+
+    def to_parser_custom_material(self) -> ctypes.c_void_p:
+        values = (ctypes.c_uint32 * 5)(*self.piece_values)
+        thresholds = (ctypes.c_uint32 * len(self.thresholds))(*self.thresholds)
+        ptr = ctypes.c_void_p(
+            PARSE_LIB.custom_material_new(values, thresholds, len(self.thresholds))
+        )
+        if ptr.value is None:
+            raise ValueError("Invalid custom material specification")
+        return ptr
+
+    def bucket_count(self) -> int:
+        ptr = self.to_parser_custom_material()
+        count = PARSE_LIB.custom_material_get_bucket_count(ptr)
+        PARSE_LIB.custom_material_drop(ptr)
+        return count
+
+
+@dataclass(frozen=True)
+class KingBucketTable:
+    # The bucket of each king square on files a-d, from a1 to d8 rank by rank
+    buckets: tuple[int, ...] = (0,) * 32
+
+    def __post_init__(self) -> None:
+        PARSE_LIB.king_bucket_table_drop(self.to_parser_king_bucket_table())
+
+    def to_parser_king_bucket_table(self) -> ctypes.c_void_p:
+        if len(self.buckets) != 32:
+            raise ValueError("A king bucket table has one bucket for each of 32 squares")
+        buckets = (ctypes.c_uint32 * 32)(*self.buckets)
+        ptr = ctypes.c_void_p(PARSE_LIB.king_bucket_table_new(buckets))
+        if ptr.value is None:
+            raise ValueError("Invalid king bucket table")
+        return ptr
+
+    def bucket_count(self) -> int:
+        ptr = self.to_parser_king_bucket_table()
+        count = PARSE_LIB.king_bucket_table_get_bucket_count(ptr)
+        PARSE_LIB.king_bucket_table_drop(ptr)
+        return count
+
+
+class FilterRule(IntEnum):
+    IN_CHECK = 0
+    EVAL = 1
+    WDL_DISAGREEMENT = 2
+    CAPTURE = 3
+
+
+class LoaderStage(IntEnum):
+    IO = 0
+    UNPACK = 1
+    FEATURIZE = 2
+    BUSY = 3
+    PARSE = 4
+
+
+class ScorePerspective(IntEnum):
+    WHITE = 0
+    SIDE_TO_MOVE = 1
+
+
+class LambdaSchedule(IntEnum):
+    CONSTANT = 0
+    PHASE = 1
+    EVAL = 2
+
+
+@dataclass
+class LoaderConfig:
+    mmap: bool = False
+    shuffle_chunks: bool = False
+    seed: int = 0
+    # Set by `BatchLoader` to the number of passes over the data made so far
+    epoch: int = 0
+    batches_per_chunk: int = 64
+    prefetch_depth: int = 1
+    # 0 shares rayon's global thread pool
+    threads: int = 0
+    collect_timings: bool = False
+    score_perspective: ScorePerspective = ScorePerspective.WHITE
+    custom_material: CustomMaterial | None = None
+    # Additional bucket outputs, stored after the reader's bucketing scheme in `Batch.buckets`
+    extra_bucketing: list[BucketingScheme | CustomMaterial] = field(default_factory=list)
+    # Replaces the king buckets of a HalfKaMirrored feature set with as many buckets
+    king_buckets: KingBucketTable | None = None
+    syzygy_path: str | None = None
+    syzygy_max_pieces: int = 0
+    syzygy_rescore_cp: bool = False
+    syzygy_win_cp: float = 2000.0
+    augment_mirror_probability: float = 0.0
+    augment_color_flip_probability: float = 0.0
+    augment_seed: int = 0
+    keep_probability: float = 1.0
+    min_fullmove: int = 0
+    filter_in_check: bool = False
+    filter_max_eval: float = float("inf")
+    filter_wdl_margin: float = float("inf")
+    filter_captures: bool = False
+    weight_early_fullmove: int = 0
+    weight_early: float = 1.0
+    weight_disagreement_margin: float = float("inf")
+    weight_disagreement: float = 1.0
+    weight_balance_buckets: bool = False
+    target_scale: float = 400.0
+    target_wdl_lambda: float = 0.0
+    target_wdl_lambda_end: float = 0.0
+    target_lambda_schedule: LambdaSchedule = LambdaSchedule.CONSTANT
+    target_eval_limit: float = 1000.0
+
+    def to_parser_config(self) -> ctypes.c_void_p:
+        ptr = ctypes.c_void_p(PARSE_LIB.loader_config_new())
+        PARSE_LIB.loader_config_set_mmap(ptr, self.mmap)
+        PARSE_LIB.loader_config_set_shuffle_chunks(ptr, self.shuffle_chunks)
+        PARSE_LIB.loader_config_set_seed(ptr, self.seed)
+        PARSE_LIB.loader_config_set_epoch(ptr, self.epoch)
+        PARSE_LIB.loader_config_set_batches_per_chunk(ptr, self.batches_per_chunk)
+        PARSE_LIB.loader_config_set_prefetch_depth(ptr, self.prefetch_depth)
+        PARSE_LIB.loader_config_set_threads(ptr, self.threads)
+        PARSE_LIB.loader_config_set_collect_timings(ptr, self.collect_timings)
+        if not PARSE_LIB.loader_config_set_score_perspective(ptr, self.score_perspective):
+            PARSE_LIB.loader_config_drop(ptr)
+            raise ValueError(f"unknown score perspective {self.score_perspective}")
+        if self.custom_material is not None:
+            custom_ptr = self.custom_material.to_parser_custom_material()
+            PARSE_LIB.loader_config_set_custom_material(ptr, custom_ptr)
+            PARSE_LIB.custom_material_drop(custom_ptr)
+        for bucketing in self.extra_bucketing:
+            if isinstance(bucketing, CustomMaterial):
+                custom_ptr = bucketing.to_parser_custom_material()
+                PARSE_LIB.loader_config_add_custom_material(ptr, custom_ptr)
+                PARSE_LIB.custom_material_drop(custom_ptr)
+            else:
+                PARSE_LIB.loader_config_add_bucketing_scheme(ptr, bucketing)
+        if self.king_buckets is not None:
+            king_buckets_ptr = self.king_buckets.to_parser_king_bucket_table()
+            PARSE_LIB.loader_config_set_king_buckets(ptr, king_buckets_ptr)
+            PARSE_LIB.king_bucket_table_drop(king_buckets_ptr)
+        if self.syzygy_path is not None:
+            PARSE_LIB.loader_config_set_syzygy_path(ptr, bytes(self.syzygy_path, "utf-8"))
+        PARSE_LIB.loader_config_set_syzygy_max_pieces(ptr, self.syzygy_max_pieces)
+        PARSE_LIB.loader_config_set_syzygy_rescore_cp(ptr, self.syzygy_rescore_cp)
+        PARSE_LIB.loader_config_set_syzygy_win_cp(ptr, self.syzygy_win_cp)
+        PARSE_LIB.loader_config_set_augment_mirror_probability(
+            ptr, self.augment_mirror_probability
+        )
+        PARSE_LIB.loader_config_set_augment_color_flip_probability(
+            ptr, self.augment_color_flip_probability
+        )
+        PARSE_LIB.loader_config_set_augment_seed(ptr, self.augment_seed)
+        PARSE_LIB.loader_config_set_keep_probability(ptr, self.keep_probability)
+        PARSE_LIB.loader_config_set_min_fullmove(ptr, self.min_fullmove)
+        PARSE_LIB.loader_config_set_filter_in_check(ptr, self.filter_in_check)
+        PARSE_LIB.loader_config_set_filter_max_eval(ptr, self.filter_max_eval)
+        PARSE_LIB.loader_config_set_filter_wdl_margin(ptr, self.filter_wdl_margin)
+        PARSE_LIB.loader_config_set_filter_captures(ptr, self.filter_captures)
+        PARSE_LIB.loader_config_set_weight_early_fullmove(ptr, self.weight_early_fullmove)
+        PARSE_LIB.loader_config_set_weight_early(ptr, self.weight_early)
+        PARSE_LIB.loader_config_set_weight_disagreement_margin(
+            ptr, self.weight_disagreement_margin
+        )
+        PARSE_LIB.loader_config_set_weight_disagreement(ptr, self.weight_disagreement)
+        PARSE_LIB.loader_config_set_weight_balance_buckets(ptr, self.weight_balance_buckets)
+        PARSE_LIB.loader_config_set_target_scale(ptr, self.target_scale)
+        PARSE_LIB.loader_config_set_target_wdl_lambda(ptr, self.target_wdl_lambda)
+        PARSE_LIB.loader_config_set_target_wdl_lambda_end(ptr, self.target_wdl_lambda_end)
+        if not PARSE_LIB.loader_config_set_target_lambda_schedule(
+            ptr, self.target_lambda_schedule
+        ):
+            PARSE_LIB.loader_config_drop(ptr)
+            raise ValueError(f"unknown lambda schedule {self.target_lambda_schedule}")
+        PARSE_LIB.loader_config_set_target_eval_limit(ptr, self.target_eval_limit)
+        return ptr
+
+
 @dataclass
 class Batch:
     stm_indices: torch.Tensor
     nstm_indices: torch.Tensor
     values: torch.Tensor
+    offsets: torch.Tensor
     cp: torch.Tensor
     wdl: torch.Tensor
+    target: torch.Tensor
+    weights: torch.Tensor
     buckets: torch.Tensor
+    stm_mirrored: torch.Tensor
+    nstm_mirrored: torch.Tensor
     size: int
 
 
@@ -106,6 +467,9 @@ class ParserBatch:
     def get_values_ptr(self) -> ctypes.pointer[ctypes.c_float]:
         return PARSE_LIB.batch_get_values_ptr(self._ptr)
 
+    def get_offsets_ptr(self) -> ctypes.pointer[ctypes.c_int64]:
+        return PARSE_LIB.batch_get_offsets_ptr(self._ptr)
+
     def get_total_features(self) -> int:
         return PARSE_LIB.batch_get_total_features(self._ptr)
 
@@ -118,9 +482,24 @@ class ParserBatch:
     def get_wdl_ptr(self) -> ctypes.pointer[ctypes.c_float]:
         return PARSE_LIB.batch_get_wdl_ptr(self._ptr)
 
+    def get_target_ptr(self) -> ctypes.pointer[ctypes.c_float]:
+        return PARSE_LIB.batch_get_target_ptr(self._ptr)
+
+    def get_weight_ptr(self) -> ctypes.pointer[ctypes.c_float]:
+        return PARSE_LIB.batch_get_weight_ptr(self._ptr)
+
     def get_bucket_ptr(self) -> ctypes.pointer[ctypes.c_int32]:
         return PARSE_LIB.batch_get_bucket_ptr(self._ptr)
 
+    def get_bucket_outputs(self) -> int:
+        return PARSE_LIB.batch_get_bucket_outputs(self._ptr)
+
+    def get_stm_mirrored_ptr(self) -> ctypes.pointer[ctypes.c_bool]:
+        return PARSE_LIB.batch_get_stm_mirrored_ptr(self._ptr)
+
+    def get_nstm_mirrored_ptr(self) -> ctypes.pointer[ctypes.c_bool]:
+        return PARSE_LIB.batch_get_nstm_mirrored_ptr(self._ptr)
+
     def to_pytorch_batch(self, device: torch.device) -> Batch:
         def to_pytorch(array: np.ndarray) -> torch.Tensor:
             tch_array = torch.from_numpy(array)
@@ -147,13 +526,43 @@ class ParserBatch:
         )
 
         batch_len = self.get_len()
+        offsets = to_pytorch(np.ctypeslib.as_array(self.get_offsets_ptr(), shape=(batch_len,)))
         cp = to_pytorch(np.ctypeslib.as_array(self.get_cp_ptr(), shape=(batch_len, 1)))
         wdl = to_pytorch(
             np.ctypeslib.as_array(self.get_wdl_ptr(), shape=(batch_len, 1))
         )
-        buckets = to_pytorch(np.ctypeslib.as_array(self.get_bucket_ptr(), shape=(batch_len, 1)))
+        target = to_pytorch(
+            np.ctypeslib.as_array(self.get_target_ptr(), shape=(batch_len, 1))
+        )
+        weights = to_pytorch(
+            np.ctypeslib.as_array(self.get_weight_ptr(), shape=(batch_len, 1))
+        )
+        buckets = to_pytorch(
+            np.ctypeslib.as_array(
+                self.get_bucket_ptr(), shape=(batch_len, self.get_bucket_outputs())
+            )
+        )
+        stm_mirrored = to_pytorch(
+            np.ctypeslib.as_array(self.get_stm_mirrored_ptr(), shape=(batch_len, 1))
+        )
+        nstm_mirrored = to_pytorch(
+            np.ctypeslib.as_array(self.get_nstm_mirrored_ptr(), shape=(batch_len, 1))
+        )
 
-        return Batch(boards_stm, boards_nstm, values, cp, wdl, buckets, batch_len)
+        return Batch(
+            boards_stm,
+            boards_nstm,
+            values,
+            offsets,
+            cp,
+            wdl,
+            target,
+            weights,
+            buckets,
+            stm_mirrored,
+            nstm_mirrored,
+            batch_len,
+        )
 
 
 class ParserBatchReader:
@@ -162,12 +571,16 @@ class ParserBatchReader:
         path: str,
         batch_size: int,
         feature_set: InputFeatureSet,
-        bucketing_scheme: BucketingScheme
+        layout: BatchLayout,
+        bucketing_scheme: BucketingScheme,
+        config: LoaderConfig | None = None,
     ) -> None:
         path_buf = ctypes.create_string_buffer(bytes(path, "utf-8"))
+        config_ptr = (config or LoaderConfig()).to_parser_config()
         self._ptr = ctypes.c_void_p(PARSE_LIB.batch_reader_new(
-            path_buf, batch_size, feature_set, bucketing_scheme
+            path_buf, batch_size, feature_set, layout, bucketing_scheme, config_ptr
         ))
+        PARSE_LIB.loader_config_drop(config_ptr)
         if self._ptr.value is None:
             raise Exception("Failed to create file reader")
 
@@ -181,6 +594,18 @@ class ParserBatchReader:
     def dataset_size(self) -> int:
         return PARSE_LIB.batch_reader_dataset_size(self._ptr)
 
+    def rescored_positions(self) -> int:
+        return PARSE_LIB.batch_reader_rescored_positions(self._ptr)
+
+    def filtered_positions(self, rule: FilterRule) -> int:
+        return PARSE_LIB.batch_reader_filtered_positions(self._ptr, rule)
+
+    def stage_seconds(self, stage: LoaderStage) -> float:
+        return PARSE_LIB.batch_reader_stage_nanos(self._ptr, stage) / 1e9
+
+    def threads(self) -> int:
+        return PARSE_LIB.batch_reader_threads(self._ptr)
+
     def drop(self) -> None:
         if self._ptr.value is not None:
             PARSE_LIB.batch_reader_drop(self._ptr)
@@ -197,14 +622,22 @@ class BatchLoader:
     def __init__(
         self,
         next_file: Callable[[], str],
-        feature_set: InputFeatureSet, bucketing_scheme: BucketingScheme,
+        feature_set: InputFeatureSet, layout: BatchLayout,
+        bucketing_scheme: BucketingScheme,
         batch_size: int,
+        config: LoaderConfig | None = None,
     ) -> None:
         self._feature_set = feature_set
+        self._layout = layout
         self._bucketing_scheme = bucketing_scheme
         self._next_file = next_file
         self._batch_size = batch_size
-        self._reader = ParserBatchReader(next_file(), batch_size, feature_set, bucketing_scheme)
+        self._config = config or LoaderConfig()
+        # Each reopened file is another pass over the data, see `LoaderConfig.epoch`
+        self._passes = 0
+        self._reader = ParserBatchReader(
+            next_file(), batch_size, feature_set, layout, bucketing_scheme, self._config
+        )
 
     def read_batch(self, device: torch.device) -> tuple[bool, Batch]:
         batch = self._reader.next_batch()
@@ -212,8 +645,11 @@ class BatchLoader:
             return False, batch.to_pytorch_batch(device)
 
         self._reader.drop()
+        self._passes += 1
+        config = replace(self._config, epoch=self._config.epoch + self._passes)
         self._reader = ParserBatchReader(
-            self._next_file(), self._batch_size, self._feature_set, self._bucketing_scheme
+            self._next_file(), self._batch_size, self._feature_set, self._layout,
+            self._bucketing_scheme, config,
         )
         return True, self.read_batch(device)[1]
 
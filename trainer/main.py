@@ -6,7 +6,7 @@ import os
 import pathlib
 import subprocess
 
-from dataloader import BatchLoader, BucketingScheme
+from dataloader import BatchLoader, BucketingScheme, LoaderConfig
 from model import (
     NnBoard768Cuda,
     NnBoard768,
@@ -38,8 +38,6 @@ def train(
     models: list[torch.nn.Module],
     optimizer: torch.optim.Optimizer,
     dataloader: BatchLoader,
-    wdl: float,
-    scale: float,
     epochs: int,
     save_epochs: int,
     lr_drop: list[int] | None = None,
@@ -81,12 +79,13 @@ def train(
                     with open(f"{nndir}/{i}-{epoch}.json", "w") as json_file:
                         json.dump(to_frozenight(param_map), json_file)
 
-        expected = torch.sigmoid(batch.cp / scale) * (1 - wdl) + batch.wdl * wdl
+        expected = batch.target
         optimizer.zero_grad()
         for model, run_loss in zip(models, running_loss):
             prediction = model(batch)
 
-            loss = torch.mean(torch.abs(prediction - expected) ** 2.6)
+            error = torch.abs(prediction - expected) ** 2.6
+            loss = torch.sum(error * batch.weights) / torch.sum(batch.weights)
             loss.backward()
 
             with torch.no_grad():
@@ -154,8 +153,10 @@ def main():
             args.data
         ][-1],
         models[0].input_feature_set(),
+        models[0].batch_layout(),
         models[0].bucketing_scheme,
-        args.batch_size
+        args.batch_size,
+        LoaderConfig(target_scale=args.scale, target_wdl_lambda=args.wdl),
     )
 
     optimizer = torch.optim.Adam([
@@ -166,8 +167,6 @@ def main():
         models,
         optimizer,
         dataloader,
-        args.wdl,
-        args.scale,
         args.epochs,
         args.save_epochs,
         lr_drop=args.lr_drop,
@@ -1,14 +1,15 @@
 import torch
 
-from dataloader import Batch, InputFeatureSet, BucketingScheme
+from dataloader import Batch, BatchLayout, InputFeatureSet, BucketingScheme
 
 
 class NnBoard768(torch.nn.Module):
     def __init__(self, ft_out: int, bucketing_scheme: BucketingScheme):
         super().__init__()
+        self.num_inputs = self.input_feature_set().num_inputs()
         self.bucketing_scheme = bucketing_scheme
         self.bucket_count = bucketing_scheme.bucket_count()
-        self.ft = torch.nn.Linear(768, ft_out)
+        self.ft = torch.nn.Linear(self.num_inputs, ft_out)
         self.out = torch.nn.Linear(ft_out * 2, 1 * self.bucket_count)
         self.idx_cache = None
 
@@ -16,10 +17,10 @@ class NnBoard768(torch.nn.Module):
         stm_indices = batch.stm_indices.reshape(-1, 2).T
         nstm_indices = batch.nstm_indices.reshape(-1, 2).T
         board_stm_sparse = torch.sparse_coo_tensor(
-            stm_indices, batch.values, (batch.size, 768)
+            stm_indices, batch.values, (batch.size, self.num_inputs)
         ).to_dense()
         board_nstm_sparse = torch.sparse_coo_tensor(
-            nstm_indices, batch.values, (batch.size, 768)
+            nstm_indices, batch.values, (batch.size, self.num_inputs)
         ).to_dense()
 
         stm_ft = self.ft(board_stm_sparse)
@@ -33,7 +34,7 @@ class NnBoard768(torch.nn.Module):
                 0, hidden.shape[0] * self.bucket_count, self.bucket_count,
                 device=batch.buckets.device
             )
-        indices = batch.buckets.flatten() + self.idx_cache
+        indices = batch.buckets[:, 0] + self.idx_cache
 
         l1_out = self.out(hidden).view(-1, 1)[indices]
 
@@ -42,13 +43,17 @@ class NnBoard768(torch.nn.Module):
     def input_feature_set(self) -> InputFeatureSet:
         return InputFeatureSet.BOARD_768
 
+    def batch_layout(self) -> BatchLayout:
+        return BatchLayout.SPARSE
+
 
 class NnHalfKP(torch.nn.Module):
     def __init__(self, ft_out: int, bucketing_scheme: BucketingScheme):
         super().__init__()
+        self.num_inputs = self.input_feature_set().num_inputs()
         self.bucketing_scheme = bucketing_scheme
         self.bucket_count = bucketing_scheme.bucket_count()
-        self.ft = torch.nn.Linear(40960, ft_out)
+        self.ft = torch.nn.Linear(self.num_inputs, ft_out)
         self.fft = torch.nn.Linear(640, ft_out)
         self.out = torch.nn.Linear(ft_out * 2, 1)
         self.idx_cache = None
@@ -58,10 +63,10 @@ class NnHalfKP(torch.nn.Module):
         stm_indices = batch.stm_indices.reshape(-1, 2).T
         nstm_indices = batch.nstm_indices.reshape(-1, 2).T
         board_stm_sparse = torch.sparse_coo_tensor(
-            stm_indices, batch.values, (batch.size, 40960)
+            stm_indices, batch.values, (batch.size, self.num_inputs)
         )
         board_nstm_sparse = torch.sparse_coo_tensor(
-            nstm_indices, batch.values, (batch.size, 40960)
+            nstm_indices, batch.values, (batch.size, self.num_inputs)
         )
 
         v_stm_indices = torch.clone(stm_indices)
@@ -85,7 +90,7 @@ class NnHalfKP(torch.nn.Module):
                 0, hidden.shape[0] * self.bucket_count, self.bucket_count,
                 device=batch.buckets.device
             )
-        indices = batch.buckets.flatten() + self.idx_cache
+        indices = batch.buckets[:, 0] + self.idx_cache
 
         l1_out = self.out(hidden).view(-1, 1)[indices]
 
@@ -94,13 +99,17 @@ class NnHalfKP(torch.nn.Module):
     def input_feature_set(self) -> InputFeatureSet:
         return InputFeatureSet.HALF_KP
 
+    def batch_layout(self) -> BatchLayout:
+        return BatchLayout.SPARSE
+
 
 class NnHalfKA(torch.nn.Module):
     def __init__(self, ft_out: int, bucketing_scheme: BucketingScheme):
         super().__init__()
+        self.num_inputs = self.input_feature_set().num_inputs()
         self.bucketing_scheme = bucketing_scheme
         self.bucket_count = bucketing_scheme.bucket_count()
-        self.ft = torch.nn.Linear(49152, ft_out)
+        self.ft = torch.nn.Linear(self.num_inputs, ft_out)
         self.fft = torch.nn.Linear(768, ft_out)
         self.out = torch.nn.Linear(ft_out * 2, 1)
         self.idx_cache = None
@@ -109,10 +118,10 @@ class NnHalfKA(torch.nn.Module):
         stm_indices = batch.stm_indices.reshape(-1, 2).T
         nstm_indices = batch.nstm_indices.reshape(-1, 2).T
         board_stm_sparse = torch.sparse_coo_tensor(
-            stm_indices, batch.values, (batch.size, 49152)
+            stm_indices, batch.values, (batch.size, self.num_inputs)
         )
         board_nstm_sparse = torch.sparse_coo_tensor(
-            nstm_indices, batch.values, (batch.size, 49152)
+            nstm_indices, batch.values, (batch.size, self.num_inputs)
         )
 
         v_stm_indices = torch.clone(stm_indices)
@@ -136,7 +145,7 @@ class NnHalfKA(torch.nn.Module):
                 0, hidden.shape[0] * self.bucket_count, self.bucket_count,
                 device=batch.buckets.device
             )
-        indices = batch.buckets.flatten() + self.idx_cache
+        indices = batch.buckets[:, 0] + self.idx_cache
 
         l1_out = self.out(hidden).view(-1, 1)[indices]
 
@@ -145,16 +154,20 @@ class NnHalfKA(torch.nn.Module):
     def input_feature_set(self) -> InputFeatureSet:
         return InputFeatureSet.HALF_KA
 
+    def batch_layout(self) -> BatchLayout:
+        return BatchLayout.SPARSE
+
 
 class NnBoard768Cuda(torch.nn.Module):
     def __init__(self, ft_out: int, bucketing_scheme: BucketingScheme):
         from cudasparse import DoubleFeatureTransformerSlice
 
         super().__init__()
+        self.num_inputs = self.input_feature_set().num_inputs()
         self.bucketing_scheme = bucketing_scheme
         self.bucket_count = bucketing_scheme.bucket_count()
-        self.max_features = InputFeatureSet.BOARD_768_CUDA.max_features()
-        self.ft = DoubleFeatureTransformerSlice(768, ft_out)
+        self.max_features = self.input_feature_set().max_features()
+        self.ft = DoubleFeatureTransformerSlice(self.num_inputs, ft_out)
         self.out = torch.nn.Linear(ft_out * 2, 1 * self.bucket_count)
         self.idx_cache = None
 
@@ -181,14 +194,17 @@ class NnBoard768Cuda(torch.nn.Module):
                 0, hidden.shape[0] * self.bucket_count, self.bucket_count,
                 device=batch.buckets.device
             )
-        indices = batch.buckets.flatten() + self.idx_cache
+        indices = batch.buckets[:, 0] + self.idx_cache
 
         l1_out = self.out(hidden).view(-1, 1)[indices]
 
         return torch.sigmoid(l1_out)
 
     def input_feature_set(self) -> InputFeatureSet:
-        return InputFeatureSet.BOARD_768_CUDA
+        return InputFeatureSet.BOARD_768
+
+    def batch_layout(self) -> BatchLayout:
+        return BatchLayout.CUDA
 
 
 class NnHalfKPCuda(torch.nn.Module):
@@ -196,10 +212,11 @@ class NnHalfKPCuda(torch.nn.Module):
         from cudasparse import DoubleFeatureTransformerSlice
 
         super().__init__()
+        self.num_inputs = self.input_feature_set().num_inputs()
         self.bucketing_scheme = bucketing_scheme
         self.bucket_count = bucketing_scheme.bucket_count()
-        self.max_features = InputFeatureSet.HALF_KP_CUDA.max_features()
-        self.ft = DoubleFeatureTransformerSlice(40960, ft_out)
+        self.max_features = self.input_feature_set().max_features()
+        self.ft = DoubleFeatureTransformerSlice(self.num_inputs, ft_out)
         self.fft = DoubleFeatureTransformerSlice(640, ft_out)
         self.out = torch.nn.Linear(ft_out * 2, 1)
         self.idx_cache = None
@@ -231,14 +248,17 @@ class NnHalfKPCuda(torch.nn.Module):
                 0, hidden.shape[0] * self.bucket_count, self.bucket_count,
                 device=batch.buckets.device
             )
-        indices = batch.buckets.flatten() + self.idx_cache
+        indices = batch.buckets[:, 0] + self.idx_cache
 
         l1_out = self.out(hidden).view(-1, 1)[indices]
 
         return torch.sigmoid(l1_out)
 
     def input_feature_set(self) -> InputFeatureSet:
-        return InputFeatureSet.HALF_KP_CUDA
+        return InputFeatureSet.HALF_KP
+
+    def batch_layout(self) -> BatchLayout:
+        return BatchLayout.CUDA
 
 
 class NnHalfKACuda(torch.nn.Module):
@@ -246,10 +266,11 @@ class NnHalfKACuda(torch.nn.Module):
         from cudasparse import DoubleFeatureTransformerSlice
 
         super().__init__()
+        self.num_inputs = self.input_feature_set().num_inputs()
         self.bucketing_scheme = bucketing_scheme
         self.bucket_count = bucketing_scheme.bucket_count()
-        self.max_features = InputFeatureSet.HALF_KA_CUDA.max_features()
-        self.ft = DoubleFeatureTransformerSlice(49152, ft_out)
+        self.max_features = self.input_feature_set().max_features()
+        self.ft = DoubleFeatureTransformerSlice(self.num_inputs, ft_out)
         self.fft = DoubleFeatureTransformerSlice(768, ft_out)
         self.out = torch.nn.Linear(ft_out * 2, 1)
         self.idx_cache = None
@@ -281,11 +302,14 @@ class NnHalfKACuda(torch.nn.Module):
                 0, hidden.shape[0] * self.bucket_count, self.bucket_count,
                 device=batch.buckets.device
             )
-        indices = batch.buckets.flatten() + self.idx_cache
+        indices = batch.buckets[:, 0] + self.idx_cache
 
         l1_out = self.out(hidden).view(-1, 1)[indices]
 
         return torch.sigmoid(l1_out)
 
     def input_feature_set(self) -> InputFeatureSet:
-        return InputFeatureSet.HALF_KA_CUDA
+        return InputFeatureSet.HALF_KA
+
+    def batch_layout(self) -> BatchLayout:
+        return BatchLayout.CUDA
@@ -0,0 +1,188 @@
+use std::io::{BufWriter, Result, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Instant;
+
+use cozy_chess::Board;
+use marlinformat::PackedBoard;
+use parse::{
+    BatchLayout, BatchReader, BucketingSchemeType, InputFeatureSetType, LoaderConfig, LoaderStage,
+};
+use rand::prelude::*;
+use structopt::StructOpt;
+
+/// Measure loader throughput for every feature set and bucketing scheme.
+#[derive(StructOpt)]
+pub struct Options {
+    /// Dataset to read, random positions are generated when omitted
+    #[structopt(long)]
+    data: Option<PathBuf>,
+
+    /// Number of random positions to generate
+    #[structopt(long, default_value = "1048576")]
+    positions: usize,
+
+    #[structopt(long, default_value = "16384")]
+    batch_size: usize,
+
+    /// Batches read per combination
+    #[structopt(long, default_value = "64")]
+    batches: usize,
+
+    #[structopt(long, default_value = "8")]
+    batches_per_chunk: u32,
+
+    /// Parser threads, 0 uses the global rayon pool
+    #[structopt(long, default_value = "0")]
+    threads: u32,
+
+    /// Batch layout, `sparse`, `cuda` or `csr`
+    #[structopt(long, default_value = "sparse")]
+    layout: Layout,
+
+    /// Only benchmark this feature set
+    #[structopt(long)]
+    feature_set: Option<String>,
+
+    /// Only benchmark this bucketing scheme
+    #[structopt(long)]
+    bucketing_scheme: Option<String>,
+
+    /// Skip feature sets whose batch buffers would take more memory than this, in MiB
+    #[structopt(long, default_value = "4096")]
+    memory_limit: usize,
+}
+
+struct Layout(BatchLayout);
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sparse" => Ok(Layout(BatchLayout::Sparse)),
+            "cuda" => Ok(Layout(BatchLayout::Cuda)),
+            "csr" => Ok(Layout(BatchLayout::Csr)),
+            _ => Err(format!(
+                "unknown layout {}, expected sparse, cuda or csr",
+                s
+            )),
+        }
+    }
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let synthetic;
+    let data = match &options.data {
+        Some(data) => data.as_path(),
+        None => {
+            synthetic = random_dataset(options.positions)?;
+            synthetic.path()
+        }
+    };
+
+    let feature_sets: Vec<_> = match &options.feature_set {
+        Some(name) => vec![InputFeatureSetType::from_name(name.as_bytes())
+            .ok_or_else(|| invalid_input(format!("unknown feature set {}", name)))?],
+        None => InputFeatureSetType::all().collect(),
+    };
+    let bucketing_schemes: Vec<_> = match &options.bucketing_scheme {
+        Some(name) => vec![BucketingSchemeType::from_name(name.as_bytes())
+            .ok_or_else(|| invalid_input(format!("unknown bucketing scheme {}", name)))?],
+        None => BucketingSchemeType::all().collect(),
+    };
+
+    let config = LoaderConfig {
+        batches_per_chunk: options.batches_per_chunk,
+        threads: options.threads,
+        collect_timings: true,
+        ..LoaderConfig::default()
+    };
+
+    println!(
+        "{:<28}{:<18}{:>12}{:>10}{:>10}{:>12}{:>8}",
+        "feature set", "bucketing", "pos/s", "io s", "unpack s", "featurize s", "util"
+    );
+    for &feature_set in &feature_sets {
+        let name = feature_set.name().to_string_lossy();
+        let bytes =
+            BatchReader::buffer_bytes(feature_set, options.layout.0, options.batch_size, &config);
+        if bytes > options.memory_limit << 20 {
+            println!(
+                "{:<28}skipped, its batch buffers need {} MiB",
+                name,
+                bytes >> 20
+            );
+            continue;
+        }
+
+        for &bucketing_scheme in &bucketing_schemes {
+            let mut reader = BatchReader::new(
+                data,
+                feature_set,
+                options.layout.0,
+                bucketing_scheme,
+                options.batch_size,
+                &config,
+            )?;
+
+            let mut positions = 0;
+            let start = Instant::now();
+            for _ in 0..options.batches {
+                match reader.next_batch() {
+                    Some(batch) => positions += batch.len(),
+                    None => break,
+                }
+            }
+            let elapsed = start.elapsed().as_secs_f64();
+
+            let stats = reader.stats();
+            let seconds = |stage| stats.stage_nanos(stage) as f64 / 1e9;
+            // Worker time over the time the pool was available while parsing
+            let available = seconds(LoaderStage::Parse) * reader.threads() as f64;
+            let utilization = seconds(LoaderStage::Busy) / available.max(1e-9);
+            println!(
+                "{:<28}{:<18}{:>12.0}{:>10.3}{:>10.3}{:>12.3}{:>7.1}%",
+                name,
+                bucketing_scheme.name().to_string_lossy(),
+                positions as f64 / elapsed,
+                seconds(LoaderStage::Io),
+                seconds(LoaderStage::Unpack),
+                seconds(LoaderStage::Featurize),
+                utilization * 100.0,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Positions from random games, which are restarted when they end or drag on
+fn random_dataset(count: usize) -> Result<tempfile::NamedTempFile> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    let mut output = BufWriter::new(file.as_file_mut());
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut board = Board::default();
+    for _ in 0..count {
+        let mut moves = vec![];
+        board.generate_moves(|piece_moves| {
+            moves.extend(piece_moves);
+            false
+        });
+        match moves.choose(&mut rng) {
+            Some(&mv) if board.fullmove_number() < 200 => board.play_unchecked(mv),
+            _ => board = Board::default(),
+        }
+        let eval = rng.gen_range(-1000..=1000);
+        let wdl = rng.gen_range(0..=2);
+        let packed = PackedBoard::pack(&board, eval, wdl, 0);
+        output.write_all(bytemuck::bytes_of(&packed))?;
+    }
+    output.flush()?;
+    drop(output);
+    Ok(file)
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
@@ -6,6 +6,7 @@ pub fn serialize_dense_i8(weights: &[Box<[f32]>], bin: &mut Vec<u8>, scale: f32)
     }
 }
 
+#[allow(dead_code)]
 pub fn serialize_flat_i8(weights: &[f32], bin: &mut Vec<u8>, scale: f32) {
     for &weight in weights {
         bin.extend(i8::try_from((weight * scale) as i64).unwrap().to_le_bytes())
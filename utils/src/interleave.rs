@@ -23,7 +23,7 @@ pub fn run(options: Options) -> Result<()> {
     let mut files: Vec<_> = options
         .files
         .iter()
-        .map(|path| File::open(path))
+        .map(File::open)
         .collect::<Result<_>>()?;
 
     let mut into = File::create(options.output)?;
@@ -33,7 +33,10 @@ pub fn run(options: Options) -> Result<()> {
     interleave(&mut into, &mut files, |progress, total| {
         if progress & 0xFFFFF == 0 {
             let proportion = progress as f64 / total as f64;
-            print!("\r\x1B[K{progress:12}/{total} ({:4.1}%)", proportion * 100.0);
+            print!(
+                "\r\x1B[K{progress:12}/{total} ({:4.1}%)",
+                proportion * 100.0
+            );
             let _ = std::io::stdout().flush();
         }
     })?;
@@ -77,10 +80,8 @@ pub fn interleave(
         into.write_all(bytemuck::bytes_of(&value))?;
 
         *count -= 1;
-        if *count == 0 {
-            if sampler.update_weights(&[(index, &0)]).is_err() {
-                return Ok(());
-            }
+        if *count == 0 && sampler.update_weights(&[(index, &0)]).is_err() {
+            return Ok(());
         }
 
         written += 1;
@@ -1,7 +1,10 @@
 use structopt::StructOpt;
 
+mod bench_loader;
 mod convert;
 mod interleave;
+mod perspective;
+mod rescore;
 mod shuffle;
 mod txt_to_data;
 
@@ -10,7 +13,9 @@ pub enum Options {
     Convert(convert::Options),
     Shuffle(shuffle::Options),
     Interleave(interleave::Options),
+    Rescore(rescore::Options),
     TxtToData(txt_to_data::Options),
+    BenchLoader(bench_loader::Options),
 }
 
 fn main() {
@@ -18,6 +23,8 @@ fn main() {
         Options::Convert(options) => convert::run(options),
         Options::Shuffle(options) => shuffle::run(options).unwrap(),
         Options::Interleave(options) => interleave::run(options).unwrap(),
+        Options::Rescore(options) => rescore::run(options).unwrap(),
         Options::TxtToData(options) => txt_to_data::run(options).unwrap(),
+        Options::BenchLoader(options) => bench_loader::run(options).unwrap(),
     }
 }
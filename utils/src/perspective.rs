@@ -0,0 +1,31 @@
+use std::str::FromStr;
+
+use cozy_chess::{Board, Color};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Perspective {
+    White,
+    SideToMove,
+}
+
+impl Perspective {
+    // The side that scores of `board` are relative to
+    pub fn color(self, board: &Board) -> Color {
+        match self {
+            Perspective::White => Color::White,
+            Perspective::SideToMove => board.side_to_move(),
+        }
+    }
+}
+
+impl FromStr for Perspective {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "white" => Ok(Perspective::White),
+            "stm" => Ok(Perspective::SideToMove),
+            _ => Err(format!("unknown perspective {}, expected white or stm", s)),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Result, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use bytemuck::Zeroable;
+use cozy_chess::Board;
+use cozy_syzygy::{Tablebase, Wdl};
+use marlinformat::PackedBoard;
+use structopt::StructOpt;
+
+use crate::perspective::Perspective;
+
+/// Rewrite the WDL labels of a dataset using syzygy tablebases.
+#[derive(StructOpt)]
+pub struct Options {
+    #[structopt(short, long)]
+    output: PathBuf,
+
+    /// Directory containing the syzygy tables
+    #[structopt(long)]
+    syzygy: PathBuf,
+
+    /// Only probe positions with at most this many pieces (0 uses the largest tables available)
+    #[structopt(long, default_value = "0")]
+    max_pieces: u32,
+
+    /// Replace the eval of decisive positions with this score minus the DTZ
+    #[structopt(long)]
+    tb_score: Option<i16>,
+
+    /// Perspective of the scores in the dataset, `white` or `stm`. Rescored positions keep it.
+    #[structopt(long, default_value = "white")]
+    perspective: Perspective,
+
+    dataset: PathBuf,
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let mut tablebase = Tablebase::new();
+    tablebase.add_directory(&options.syzygy)?;
+    let max_pieces = match options.max_pieces {
+        0 => tablebase.max_pieces(),
+        n => n.min(tablebase.max_pieces()),
+    };
+
+    let mut input = File::open(options.dataset)?;
+    let total = input.seek(SeekFrom::End(0))? / std::mem::size_of::<PackedBoard>() as u64;
+    input.rewind()?;
+    let mut input = BufReader::new(input);
+    let mut output = BufWriter::new(File::create(options.output)?);
+
+    let start = Instant::now();
+
+    let mut probed = 0u64;
+    let mut wdl_changed = 0u64;
+    let mut eval_changed = 0u64;
+
+    for progress in 1..=total {
+        let mut value = PackedBoard::zeroed();
+        input.read_exact(bytemuck::bytes_of_mut(&mut value))?;
+
+        if let Some((board, eval, wdl, extra)) = value.unpack() {
+            let tb_board = match board.occupied().len() <= max_pieces {
+                true => syzygy_board(&board),
+                false => None,
+            };
+            if let Some(tb_board) = tb_board {
+                if let Some(tb_wdl) = tablebase.probe_wdl(&tb_board) {
+                    probed += 1;
+
+                    let (new_eval, new_wdl) = rescored(
+                        &board,
+                        eval,
+                        tb_wdl,
+                        || tablebase.probe_dtz(&tb_board),
+                        options.perspective,
+                        options.tb_score,
+                    );
+
+                    if new_wdl != wdl {
+                        wdl_changed += 1;
+                    }
+                    if new_eval != eval {
+                        eval_changed += 1;
+                    }
+                    value = PackedBoard::pack(&board, new_eval, new_wdl, extra);
+                }
+            }
+        }
+
+        output.write_all(bytemuck::bytes_of(&value))?;
+
+        if progress & 0xFFFFF == 0 {
+            let proportion = progress as f64 / total as f64;
+            print!(
+                "\r\x1B[K{progress:12}/{total} ({:4.1}%)",
+                proportion * 100.0
+            );
+            let _ = std::io::stdout().flush();
+        }
+    }
+    println!();
+    println!("Done ({:.1?}).", start.elapsed());
+
+    let percent = |count: u64| match probed {
+        0 => 0.0,
+        _ => count as f64 / probed as f64 * 100.0,
+    };
+    println!("Probed {probed} of {total} positions.");
+    println!(
+        "Game result disagreed with the tablebase in {wdl_changed} positions ({:.1}%).",
+        percent(wdl_changed)
+    );
+    if options.tb_score.is_some() {
+        println!(
+            "Changed the eval of {eval_changed} positions ({:.1}%).",
+            percent(eval_changed)
+        );
+    }
+
+    Ok(())
+}
+
+// The eval and wdl of a position from its tablebase result, in the perspective of the dataset
+fn rescored(
+    board: &Board,
+    eval: i16,
+    tb_wdl: Wdl,
+    dtz: impl FnOnce() -> Option<i32>,
+    perspective: Perspective,
+    tb_score: Option<i16>,
+) -> (i16, u8) {
+    // Cursed wins and blessed losses are draws under the 50 move rule
+    let stm_wdl = match tb_wdl {
+        Wdl::Win => 2,
+        Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => 1,
+        Wdl::Loss => 0,
+    };
+    let new_wdl = match perspective.color(board) == board.side_to_move() {
+        true => stm_wdl,
+        false => 2 - stm_wdl,
+    };
+
+    let new_eval = match tb_score {
+        Some(_) if new_wdl == 1 => 0,
+        Some(tb_score) => {
+            let dtz = dtz().map_or(0, |dtz| dtz.unsigned_abs().min(i16::MAX as u32));
+            let score = tb_score.saturating_sub(dtz as i16);
+            match new_wdl {
+                2 => score,
+                _ => -score,
+            }
+        }
+        None => eval,
+    };
+    (new_eval, new_wdl)
+}
+
+// cozy-syzygy takes boards from an older cozy-chess, so positions are passed over as (Shredder) FEN
+fn syzygy_board(board: &Board) -> Option<syzygy_chess::Board> {
+    syzygy_chess::Board::from_fen(&format!("{board:#}"), true).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_dtz() -> Option<i32> {
+        unreachable!()
+    }
+
+    #[test]
+    fn keeps_the_dataset_perspective() {
+        let white: Board = "8/8/8/4k3/8/8/8/KQ6 w - - 0 1".parse().unwrap();
+        let black: Board = "8/8/8/4k3/8/8/8/KQ6 b - - 0 1".parse().unwrap();
+        let white_scores = Perspective::White;
+        let stm_scores = Perspective::SideToMove;
+
+        let rescore =
+            |board, tb_wdl, perspective| rescored(board, 50, tb_wdl, no_dtz, perspective, None);
+        assert_eq!(rescore(&white, Wdl::Win, white_scores), (50, 2));
+        assert_eq!(rescore(&white, Wdl::Win, stm_scores), (50, 2));
+        assert_eq!(rescore(&black, Wdl::Loss, white_scores), (50, 2));
+        assert_eq!(rescore(&black, Wdl::Loss, stm_scores), (50, 0));
+        assert_eq!(rescore(&black, Wdl::CursedWin, stm_scores), (50, 1));
+        assert_eq!(rescore(&black, Wdl::BlessedLoss, white_scores), (50, 1));
+    }
+
+    #[test]
+    fn tb_score_counts_down_with_dtz() {
+        let black: Board = "8/8/8/4k3/8/8/8/KQ6 b - - 0 1".parse().unwrap();
+        let white_scores = Perspective::White;
+        let stm_scores = Perspective::SideToMove;
+
+        let rescore = |tb_wdl, dtz: i32, perspective| {
+            rescored(
+                &black,
+                50,
+                tb_wdl,
+                move || Some(dtz),
+                perspective,
+                Some(20000),
+            )
+        };
+        assert_eq!(rescore(Wdl::Loss, -12, white_scores), (19988, 2));
+        assert_eq!(rescore(Wdl::Loss, -12, stm_scores), (-19988, 0));
+        assert_eq!(rescore(Wdl::Win, 7, stm_scores), (19993, 2));
+        assert_eq!(rescore(Wdl::Win, 7, white_scores), (-19993, 0));
+        assert_eq!(
+            rescored(&black, 50, Wdl::Draw, no_dtz, stm_scores, Some(20000)),
+            (0, 1)
+        );
+        assert_eq!(
+            rescored(&black, 50, Wdl::Win, || None, stm_scores, Some(20000)),
+            (20000, 2)
+        );
+    }
+}
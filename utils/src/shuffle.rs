@@ -49,7 +49,7 @@ pub fn run(options: Options) -> Result<()> {
         return Ok(());
     }
 
-    let block_count = (positions + options.block_size - 1) / options.block_size;
+    let block_count = positions.div_ceil(options.block_size);
 
     let (send, mut recv) = std::sync::mpsc::sync_channel(options.group_size as usize);
 
@@ -74,7 +74,7 @@ pub fn run(options: Options) -> Result<()> {
     let mut level = 0;
     loop {
         level += 1;
-        items = (items + options.group_size - 1) / options.group_size;
+        items = items.div_ceil(options.group_size);
         if items == 1 {
             break;
         }
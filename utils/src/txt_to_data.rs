@@ -6,12 +6,23 @@ use cozy_chess::Board;
 use marlinformat::PackedBoard;
 use structopt::StructOpt;
 
+use crate::perspective::Perspective;
+
 /// Convert legacy text data format to marlinformat.
 #[derive(StructOpt)]
 pub struct Options {
     #[structopt(short, long)]
     output: PathBuf,
 
+    /// Perspective of the scores in the text file, `white` or `stm`.
+    #[structopt(long, default_value = "white")]
+    input_perspective: Perspective,
+
+    /// Perspective to store the scores in, `white` or `stm`. Marlinformat data is white relative
+    /// unless the reader is told otherwise.
+    #[structopt(long, default_value = "white")]
+    output_perspective: Perspective,
+
     txt_file: PathBuf,
 }
 
@@ -32,6 +43,13 @@ pub fn run(options: Options) -> Result<()> {
             let cp: f32 = cp.parse().ok()?;
             let wdl: f32 = wdl.parse().ok()?;
 
+            let flip = options.input_perspective.color(&board)
+                != options.output_perspective.color(&board);
+            let (cp, wdl) = match flip {
+                true => (-cp, 1.0 - wdl),
+                false => (cp, wdl),
+            };
+
             if !had_non_integer_cp && cp.floor() != cp {
                 println!("Warning: dataset contains non-integer centipawn values. These will be truncated.");
                 had_non_integer_cp = true;